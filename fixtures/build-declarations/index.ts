@@ -0,0 +1,12 @@
+interface Options {
+  name: string;
+}
+
+class Greeter {
+  constructor(public options: Options) {
+  }
+}
+
+export function createGreeter(options: Options): Greeter {
+  return new Greeter(options);
+}
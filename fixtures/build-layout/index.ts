@@ -0,0 +1,3 @@
+import {format} from './utils/format';
+
+export const greeting = format(`world`);
@@ -0,0 +1,3 @@
+export function format(name: string) {
+  return `Hello ${name}`;
+}
@@ -0,0 +1 @@
+export {secret as hello} from './internal/secret';
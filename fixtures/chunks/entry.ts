@@ -0,0 +1,4 @@
+import {suffix} from './local';
+import {greet}  from './vendor/greet';
+
+export const message = greet(`world`) + suffix;
@@ -0,0 +1,5 @@
+import styled from '@emotion/styled';
+
+export const Button = styled.button`
+  color: red;
+`;
@@ -0,0 +1,5 @@
+import styled from 'styled-components';
+
+export const Button = styled.button`
+  color: red;
+`;
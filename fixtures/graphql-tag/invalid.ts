@@ -0,0 +1,7 @@
+import gql from 'graphql-tag';
+
+export const query = gql`
+  query Search {
+    search(
+  }
+`;
@@ -0,0 +1,16 @@
+import gql from 'graphql-tag';
+
+export const query = gql`
+  query Search($id: ID = 1, $limit: Int!) {
+    search(id: $id, limit: $limit, seed: 1234567890, ratio: 1.5e3, text: """
+      hello
+        world
+    """) {
+      ...Result
+    }
+  }
+
+  fragment Result on SearchResult {
+    title
+  }
+`;
@@ -0,0 +1,7 @@
+import gql from 'graphql-tag';
+
+export const typeDefs = gql`
+  type Query {
+    hello: String
+  }
+`;
@@ -0,0 +1,4 @@
+import text    from './query-assets/hello.txt?raw';
+import dataUrl from './query-assets/hello.txt?inline';
+
+export {text, dataUrl};
@@ -0,0 +1,3 @@
+import url from './query-assets/hello.txt?transform=url';
+
+export {url};
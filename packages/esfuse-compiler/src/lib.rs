@@ -1,13 +1,16 @@
 #![deny(clippy::all)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use arca::Path;
 use esfuse::types::ModuleLocator;
+use esfuse::utils::cancellation::CancellationToken;
 use napi::bindgen_prelude::*;
-use napi::threadsafe_function::{ThreadsafeFunction, ErrorStrategy};
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use napi_derive::napi;
 
 extern crate napi_derive;
@@ -15,6 +18,8 @@ extern crate napi_derive;
 #[napi(object)]
 pub struct ProjectHook {
   pub regexp: String,
+  pub params: Option<Vec<esfuse::types::OptionStringKeyValue>>,
+  pub timeout: Option<u32>,
   pub cb: JsFunction,
 }
 
@@ -25,16 +30,356 @@ pub struct ProjectDefinition {
 
   pub on_resolve: Vec<ProjectHook>,
   pub on_fetch: Vec<ProjectHook>,
+  pub on_transform: Vec<ProjectHook>,
+  pub on_render_chunk: Vec<ProjectHook>,
+  pub on_generate_bundle: Vec<ProjectHook>,
+
+  pub transforms: HashMap<String, JsFunction>,
+
+  pub hook_timeout: Option<u32>,
 }
 
 #[derive(Clone)]
 struct HookData<T: 'static> {
+  name: String,
   cb: ThreadsafeFunction<T, ErrorStrategy::Fatal>,
 }
 
+// The source is what the hook was processing (the issuer for the resolve
+// hooks, the module or the bundle entry for the others)
+fn get_hook_highlight(hook_name: &str, source: Option<String>, span: Option<esfuse::types::Span>) -> esfuse::utils::errors::Highlight {
+  esfuse::utils::errors::Highlight {
+    source,
+    subject: Some(hook_name.to_string()),
+    label: None,
+    span,
+  }
+}
+
+fn get_hook_result<T>(highlight: esfuse::utils::errors::Highlight, value: Option<T>, error: Option<esfuse::CompilationError>) -> std::result::Result<T, esfuse::CompilationError> {
+  match (value, error) {
+    (Some(value), _) => Ok(value),
+    (None, Some(error)) => Err(error),
+    (None, None) => Err(esfuse::CompilationError::from_str_with_highlight("The hook returned neither a value nor an error", highlight)),
+  }
+}
+
+fn get_hook_regexp(hook_name: &str, regexp: &str) -> napi::Result<esfuse::utils::Regex> {
+  esfuse::utils::Regex::from_str(regexp).map_err(|err| {
+    napi::Error::new(Status::InvalidArg, format!("Invalid regexp in {} ({})", hook_name, err))
+  })
+}
+
+type ResolveNext
+  = esfuse::types::HookNext<esfuse::types::OnResolveArgs, esfuse::types::OnResolveResult>;
+type FetchNext
+  = esfuse::types::HookNext<esfuse::types::OnFetchArgs, esfuse::types::OnFetchResult>;
+
+// Continuations can't cross the napi boundary, so JS hooks receive an id
+// they can pass back to resolveNext / fetchNext while their promise is pending
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+static RESOLVE_NEXT: Mutex<BTreeMap<u32, ResolveNext>> = Mutex::new(BTreeMap::new());
+static FETCH_NEXT: Mutex<BTreeMap<u32, FetchNext>> = Mutex::new(BTreeMap::new());
+
+// Removes the continuation once the hook settles, or once its future is
+// dropped (for instance when the hook times out)
+struct NextRegistration<T: Clone + 'static> {
+  registry: &'static Mutex<BTreeMap<u32, T>>,
+  id: u32,
+}
+
+impl<T: Clone + 'static> Drop for NextRegistration<T> {
+  fn drop(&mut self) {
+    self.registry.lock().unwrap().remove(&self.id);
+  }
+}
+
+fn register_next<T: Clone>(registry: &'static Mutex<BTreeMap<u32, T>>, next: T) -> NextRegistration<T> {
+  let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+  registry.lock().unwrap().insert(id, next);
+
+  NextRegistration {
+    registry,
+    id,
+  }
+}
+
+fn get_next<T: Clone>(registry: &Mutex<BTreeMap<u32, T>>, id: u32) -> Option<T> {
+  registry.lock().unwrap().get(&id).cloned()
+}
+
+enum JsHook {
+  Resolve(ThreadsafeFunction<(esfuse::types::OnResolveArgs, u32), ErrorStrategy::Fatal>),
+  Fetch(ThreadsafeFunction<(esfuse::types::OnFetchArgs, u32), ErrorStrategy::Fatal>),
+  Transform(ThreadsafeFunction<esfuse::types::OnTransformHookArgs, ErrorStrategy::Fatal>),
+  RenderChunk(ThreadsafeFunction<esfuse::types::OnRenderChunkArgs, ErrorStrategy::Fatal>),
+  GenerateBundle(ThreadsafeFunction<esfuse::types::OnGenerateBundleArgs, ErrorStrategy::Fatal>),
+}
+
+// Each JS hook is registered as its own plugin, so that they keep the
+// order in which they were declared and get their own stats
+struct JsPlugin {
+  name: String,
+  kind: esfuse::types::PluginHookKind,
+  filter: esfuse::types::PluginFilter,
+  timeout: Option<Duration>,
+  hook: JsHook,
+}
+
+fn get_js_plugin(hook_name: String, hook: &ProjectHook, js_hook: JsHook) -> napi::Result<Arc<dyn esfuse::EsfusePlugin>> {
+  let kind = match &js_hook {
+    JsHook::Resolve(_) => esfuse::types::PluginHookKind::Resolve,
+    JsHook::Fetch(_) => esfuse::types::PluginHookKind::Fetch,
+    JsHook::Transform(_) => esfuse::types::PluginHookKind::Transform,
+    JsHook::RenderChunk(_) => esfuse::types::PluginHookKind::RenderChunk,
+    JsHook::GenerateBundle(_) => esfuse::types::PluginHookKind::GenerateBundle,
+  };
+
+  Ok(Arc::new(JsPlugin {
+    filter: esfuse::types::PluginFilter {
+      regexp: get_hook_regexp(&hook_name, &hook.regexp)?,
+      params: hook.params.clone().unwrap_or_default(),
+    },
+    name: hook_name,
+    kind,
+    timeout: hook.timeout.map(|ms| Duration::from_millis(ms as u64)),
+    hook: js_hook,
+  }))
+}
+
+// The JS results all have the same shape, but napi needs distinct types
+trait JsHookResult {
+  type Value;
+
+  fn into_parts(self) -> (Option<Self::Value>, Option<esfuse::CompilationError>, Vec<esfuse::types::ModuleLocator>);
+}
+
+macro_rules! impl_js_hook_result {
+  ($($name:ident => $value:ident),*) => {
+    $(impl JsHookResult for $name {
+      type Value = esfuse::types::$value;
+
+      fn into_parts(self) -> (Option<Self::Value>, Option<esfuse::CompilationError>, Vec<esfuse::types::ModuleLocator>) {
+        (self.value, self.error, self.dependencies)
+      }
+    })*
+  };
+}
+
+impl_js_hook_result!(
+  ResolveResult => OnResolveResultData,
+  FetchResult => OnFetchResultData,
+  TransformResult => OnTransformResultData,
+  RenderChunkResult => OnRenderChunkResultData,
+  BundleResult => OnBundleResultData
+);
+
+async fn call_js_hook<TArgs, TJsRes, TRes>(highlight: esfuse::utils::errors::Highlight, cb: &ThreadsafeFunction<TArgs, ErrorStrategy::Fatal>, args: TArgs) -> Option<TRes>
+where TArgs: 'static, TJsRes: JsHookResult<Value = TRes::Value> + FromNapiValue + Send + 'static, TRes: esfuse::types::HookResult {
+  let hook_res = match cb.call_async::<Promise<Option<TJsRes>>>(args).await {
+    Ok(promise) => promise.await,
+    Err(err) => Err(err),
+  };
+
+  match hook_res {
+    Ok(hook_maybe) => hook_maybe.map(|hook_res| {
+      let (value, error, dependencies)
+        = hook_res.into_parts();
+
+      TRes::new(get_hook_result(highlight, value, error), dependencies)
+    }),
+
+    Err(err) => Some(TRes::from_error(esfuse::CompilationError::from_string_with_highlight(err.reason, highlight))),
+  }
+}
+
+impl esfuse::EsfusePlugin for JsPlugin {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn hooks(&self) -> &[esfuse::types::PluginHookKind] {
+    std::slice::from_ref(&self.kind)
+  }
+
+  fn filter(&self) -> Option<&esfuse::types::PluginFilter> {
+    Some(&self.filter)
+  }
+
+  fn timeout(&self) -> Option<Duration> {
+    self.timeout
+  }
+
+  fn resolve(self: Arc<Self>, call: esfuse::types::HookCall<esfuse::types::OnResolveArgs, esfuse::types::OnResolveResult>) -> esfuse::utils::BoxedFuture<Option<esfuse::types::OnResolveResult>> {
+    Box::pin(async move {
+      let cb = match &self.hook {
+        JsHook::Resolve(cb) => cb,
+        _ => return None,
+      };
+
+      let highlight
+        = get_hook_highlight(&self.name, call.args.issuer.as_ref().map(|locator| locator.url.clone()), call.args.span.clone());
+      let next_registration
+        = register_next(&RESOLVE_NEXT, call.next);
+
+      call_js_hook::<_, ResolveResult, _>(highlight, cb, (call.args, next_registration.id)).await
+    })
+  }
+
+  fn fetch(self: Arc<Self>, call: esfuse::types::HookCall<esfuse::types::OnFetchArgs, esfuse::types::OnFetchResult>) -> esfuse::utils::BoxedFuture<Option<esfuse::types::OnFetchResult>> {
+    Box::pin(async move {
+      let cb = match &self.hook {
+        JsHook::Fetch(cb) => cb,
+        _ => return None,
+      };
+
+      let highlight
+        = get_hook_highlight(&self.name, Some(call.args.locator.url.clone()), None);
+      let next_registration
+        = register_next(&FETCH_NEXT, call.next);
+
+      call_js_hook::<_, FetchResult, _>(highlight, cb, (call.args, next_registration.id)).await
+    })
+  }
+
+  fn transform(self: Arc<Self>, args: esfuse::types::OnTransformHookArgs) -> esfuse::utils::BoxedFuture<Option<esfuse::types::OnTransformResult>> {
+    Box::pin(async move {
+      let cb = match &self.hook {
+        JsHook::Transform(cb) => cb,
+        _ => return None,
+      };
+
+      let highlight
+        = get_hook_highlight(&self.name, Some(args.locator.url.clone()), None);
+
+      call_js_hook::<_, TransformResult, _>(highlight, cb, args).await
+    })
+  }
+
+  fn render_chunk(self: Arc<Self>, args: esfuse::types::OnRenderChunkArgs) -> esfuse::utils::BoxedFuture<Option<esfuse::types::OnRenderChunkResult>> {
+    Box::pin(async move {
+      let cb = match &self.hook {
+        JsHook::RenderChunk(cb) => cb,
+        _ => return None,
+      };
+
+      let highlight
+        = get_hook_highlight(&self.name, Some(args.entry.clone()), None);
+
+      call_js_hook::<_, RenderChunkResult, _>(highlight, cb, args).await
+    })
+  }
+
+  fn generate_bundle(self: Arc<Self>, args: esfuse::types::OnGenerateBundleArgs) -> esfuse::utils::BoxedFuture<Option<esfuse::types::OnBundleResult>> {
+    Box::pin(async move {
+      let cb = match &self.hook {
+        JsHook::GenerateBundle(cb) => cb,
+        _ => return None,
+      };
+
+      let highlight
+        = get_hook_highlight(&self.name, Some(args.bundle.entry.clone()), None);
+
+      call_js_hook::<_, BundleResult, _>(highlight, cb, args).await
+    })
+  }
+}
+
+fn register_js_hooks<T: 'static, V: ToNapiValue>(project: &mut esfuse::Project, hooks: Vec<ProjectHook>, label: &str, get_call_args: fn(ThreadSafeCallContext<T>) -> napi::Result<Vec<V>>, get_js_hook: fn(ThreadsafeFunction<T, ErrorStrategy::Fatal>) -> JsHook) -> napi::Result<()> {
+  for (index, hook) in hooks.into_iter().enumerate() {
+    let tsfn
+      = hook.cb.create_threadsafe_function(0, get_call_args)?;
+
+    project.register_plugin(get_js_plugin(format!("{}[{}] ({})", label, index, hook.regexp), &hook, get_js_hook(tsfn))?);
+  }
+
+  Ok(())
+}
+
+// AbortSignals can't be sent to async methods, so the JS side registers a
+// cancellation, forwards the abort events to it, and releases it afterwards
+static CANCELLATION_ID: AtomicU32 = AtomicU32::new(0);
+static CANCELLATIONS: Mutex<BTreeMap<u32, CancellationToken>> = Mutex::new(BTreeMap::new());
+
+fn get_cancellation(id: Option<u32>) -> CancellationToken {
+  id.and_then(|id| CANCELLATIONS.lock().unwrap().get(&id).cloned())
+    .unwrap_or_default()
+}
+
+type ProgressCallback = ThreadsafeFunction<esfuse::types::OnBatchProgress, ErrorStrategy::Fatal>;
+type ModuleCallback = ThreadsafeFunction<BatchModuleResult, ErrorStrategy::Fatal>;
+type MappingsCallback = ThreadsafeFunction<HashMap<String, HashMap<String, String>>, ErrorStrategy::Fatal>;
+
+fn get_batch_control(cancellation: Option<u32>, on_progress: Option<ProgressCallback>, on_module: Option<ModuleCallback>, on_mappings: Option<MappingsCallback>) -> esfuse::types::BatchControl {
+  esfuse::types::BatchControl {
+    cancellation: get_cancellation(cancellation),
+    on_progress: on_progress.map(|tsfn| -> esfuse::types::OnBatchProgressHook {
+      Arc::new(move |progress| {
+        tsfn.call(progress, ThreadsafeFunctionCallMode::NonBlocking);
+      })
+    }),
+    on_module: on_module.map(|tsfn| -> esfuse::types::OnBatchModuleHook {
+      Arc::new(move |res: &esfuse::types::OnBatchModuleResult| {
+        tsfn.call(get_batch_module_result(res.clone()), ThreadsafeFunctionCallMode::NonBlocking);
+      })
+    }),
+    on_mappings: on_mappings.map(|tsfn| -> esfuse::types::OnBatchMappingsHook {
+      Arc::new(move |mappings: &HashMap<String, HashMap<String, String>>| {
+        tsfn.call(mappings.clone(), ThreadsafeFunctionCallMode::NonBlocking);
+      })
+    }),
+  }
+}
+
+fn get_batch_module_result(res: esfuse::types::OnBatchModuleResult) -> BatchModuleResult {
+  let module_res = res.result.and_then(|module| {
+    module.bytes().map(|bytes| (module, esfuse::utils::get_integrity(bytes)))
+  });
+
+  match module_res {
+    Ok((module, integrity)) => BatchModuleResult {
+      locator: res.locator,
+      value: Some(BatchModule {
+        imaginary_path: module.imaginary_path,
+        mime_type: module.mime_type,
+        code: module.code,
+        map: module.map.map(|mut source_map| source_map.to_json(None).expect("Should have been able to serialize the source map")),
+        declaration: module.declaration,
+        declaration_error: module.declaration_error,
+        integrity,
+      }),
+      error: None,
+      dependencies: res.dependencies,
+    },
+    Err(error) => BatchModuleResult {
+      locator: res.locator,
+      value: None,
+      error: Some(error),
+      dependencies: res.dependencies,
+  
+    },
+  }
+}
+
+// Operations take a snapshot of the project when they start, so swapping
+// it only affects the operations started afterwards
 #[napi]
 pub struct ProjectHandle {
-  project: Arc<esfuse::Project>,
+  project: RwLock<Arc<esfuse::Project>>,
+}
+
+impl ProjectHandle {
+  fn snapshot(&self) -> Arc<esfuse::Project> {
+    self.project.read().unwrap().clone()
+  }
+
+  fn swap(&self, mut project: esfuse::Project) {
+    let mut current
+      = self.project.write().unwrap();
+
+    project.inherit_hook_stats(&current);
+    *current = Arc::new(project);
+  }
 }
 
 #[napi(object)]
@@ -55,32 +400,45 @@ pub struct GetFromUrlRequest {
 #[napi]
 impl ProjectHandle {
   #[napi(factory)]
-  pub fn create(definition: ProjectDefinition) -> Self {
-    let project = Arc::new(
-      use_project(definition),
-    );
+  pub fn create(definition: ProjectDefinition) -> Result<Self> {
+    let project = RwLock::new(Arc::new(
+      use_project(definition)?,
+    ));
 
-    Self {
+    Ok(Self {
       project,
-    }
+    })
+  }
+
+  #[napi]
+  pub fn reconfigure(&self, definition: ProjectDefinition) -> Result<()> {
+    self.swap(use_project(definition)?);
+    Ok(())
   }
 
+  // The hooks hold references to JS functions; releasing them lets the
+  // process exit once the in-flight operations are done
   #[napi]
-  pub fn dispose(&mut self) {
-    let project = Arc::get_mut(&mut self.project).unwrap();
+  pub fn dispose(&self) {
+    let root
+      = self.snapshot().root.clone().into_owned();
 
-    project.on_resolve.clear();
-    project.on_fetch.clear();
+    self.swap(esfuse::Project::new(&root));
+  }
+
+  #[napi]
+  pub fn get_hook_stats(&self) -> Vec<esfuse::types::HookStats> {
+    self.snapshot().hook_stats()
   }
 
   #[napi]
   pub fn get_path_from_locator(&self, req: GetFromLocatorRequest) -> Option<Path> {
-    req.locator.physical_path(&self.project)
+    req.locator.physical_path(&self.snapshot())
   }
 
   #[napi]
   pub fn get_locator_from_path(&self, path: Path) -> Option<esfuse::types::ModuleLocator> {
-    self.project.locator_from_path(&path, &vec![])
+    self.snapshot().locator_from_path(&path, &vec![])
   }
 
   #[napi]
@@ -90,18 +448,18 @@ impl ProjectHandle {
 
   #[napi]
   pub fn get_ns_qualified_from_path(&self, path: Path) -> Option<String> {
-    self.project.ns_qualified_from_path(&path)
+    self.snapshot().ns_qualified_from_path(&path)
   }
 
   #[napi]
   pub fn get_path_from_ns_qualified(&self, str: String) -> Path {
-    self.project.path_from_ns_qualified(&str)
+    self.snapshot().path_from_ns_qualified(&str)
   }
 
   #[napi]
   pub async fn resolve(&self, args: esfuse::types::OnResolveArgs) -> ResolveResult {
     let res = esfuse::actions::resolve::resolve(
-      &self.project,
+      &self.snapshot(),
       args,
     ).await;
 
@@ -111,10 +469,40 @@ impl ProjectHandle {
     }
   }
 
+  #[napi]
+  pub async fn resolve_next(&self, id: u32, args: esfuse::types::OnResolveArgs) -> ResolveResult {
+    let next = match get_next(&RESOLVE_NEXT, id) {
+      Some(next) => next,
+      None => return ResolveResult { value: None, error: Some(esfuse::CompilationError::from_str("The resolve continuation has expired")), dependencies: vec![] },
+    };
+
+    let res = next(args).await;
+
+    match res.result {
+      Ok(value) => ResolveResult { value: Some(value), error: None, dependencies: res.dependencies },
+      Err(error) => ResolveResult { value: None, error: Some(error), dependencies: res.dependencies },
+    }
+  }
+
+  #[napi]
+  pub async fn fetch_next(&self, id: u32, args: esfuse::types::OnFetchArgs) -> FetchResult {
+    let next = match get_next(&FETCH_NEXT, id) {
+      Some(next) => next,
+      None => return FetchResult { value: None, error: Some(esfuse::CompilationError::from_str("The fetch continuation has expired")), dependencies: vec![] },
+    };
+
+    let res = next(args).await;
+
+    match res.result {
+      Ok(value) => FetchResult { value: Some(value), error: None, dependencies: res.dependencies },
+      Err(error) => FetchResult { value: None, error: Some(error), dependencies: res.dependencies },
+    }
+  }
+
   #[napi]
   pub fn transform_no_hooks(&self, args: esfuse::types::OnTransformArgs) -> TransformResult {
     let res = esfuse::actions::transform::transform_no_hooks(
-      &self.project,
+      &self.snapshot(),
       args,
     );
 
@@ -127,7 +515,7 @@ impl ProjectHandle {
   #[napi]
   pub async fn transform(&self, args: esfuse::types::OnTransformArgs) -> TransformResult {
     let res = esfuse::actions::transform::transform(
-      &self.project,
+      &self.snapshot(),
       args,
     ).await;
 
@@ -138,10 +526,30 @@ impl ProjectHandle {
   }
 
   #[napi]
-  pub async fn bundle(&self, args: esfuse::types::OnBundleArgs) -> BundleResult {
+  pub fn create_cancellation(&self) -> u32 {
+    let id = CANCELLATION_ID.fetch_add(1, Ordering::Relaxed);
+    CANCELLATIONS.lock().unwrap().insert(id, CancellationToken::default());
+    id
+  }
+
+  #[napi]
+  pub fn cancel(&self, id: u32) {
+    if let Some(cancellation) = CANCELLATIONS.lock().unwrap().get(&id) {
+      cancellation.cancel();
+    }
+  }
+
+  #[napi]
+  pub fn release_cancellation(&self, id: u32) {
+    CANCELLATIONS.lock().unwrap().remove(&id);
+  }
+
+  #[napi]
+  pub async fn bundle(&self, args: esfuse::types::OnBundleArgs, cancellation: Option<u32>, on_progress: Option<ProgressCallback>) -> BundleResult {
     let res = esfuse::actions::bundle::bundle(
-      self.project.clone(),
+      self.snapshot(),
       args,
+      &get_batch_control(cancellation, on_progress, None, None),
     ).await;
 
     match res.result {
@@ -151,151 +559,107 @@ impl ProjectHandle {
   }
 
   #[napi]
-  pub async fn batch(&self, args: esfuse::types::OnBatchArgs) -> Vec<BatchModuleResult> {
+  pub async fn build(&self, args: esfuse::types::OnBuildArgs, cancellation: Option<u32>, on_progress: Option<ProgressCallback>, on_module: Option<ModuleCallback>) -> BuildResult {
+    let res = esfuse::actions::build::build(
+      self.snapshot(),
+      args,
+      &get_batch_control(cancellation, on_progress, on_module, None),
+    ).await;
+
+    match res.result {
+      Ok(value) => BuildResult { value: Some(value), error: None, dependencies: res.dependencies },
+      Err(error) => BuildResult { value: None, error: Some(error), dependencies: res.dependencies },
+    }
+  }
+
+  // The modules have already been sent when streaming them, so we only
+  // return a summary rather than sending them a second time
+  #[napi]
+  pub async fn batch(&self, args: esfuse::types::OnBatchArgs, cancellation: Option<u32>, on_progress: Option<ProgressCallback>, on_module: Option<ModuleCallback>, on_mappings: Option<MappingsCallback>) -> Result<Either<Vec<BatchModuleResult>, BatchSummary>> {
+    let is_streamed
+      = on_module.is_some();
+
     let res = esfuse::actions::batch::batch(
-      self.project.clone(),
+      self.snapshot(),
       args,
+      &get_batch_control(cancellation, on_progress, on_module, on_mappings),
     ).await;
 
-    res.results.into_values().map(|res| {
-      match res.result {
-        Ok(module) => BatchModuleResult {
-          locator: res.locator,
-          value: Some(BatchModule {
-            imaginary_path: module.imaginary_path,
-            mime_type: module.mime_type,
-            code: module.code,
-            map: module.map.map(|mut source_map| source_map.to_json(None).expect("Should have been able to serialize the source map")),
-          }),
-          error: None,
-          dependencies: res.dependencies,
-        },
-        Err(error) => BatchModuleResult {
-          locator: res.locator,
-          value: None,
-          error: Some(error),
-          dependencies: res.dependencies,
-  
-        },
-      }
-    }).collect()
+    if res.cancelled {
+      return Err(napi::Error::new(Status::Cancelled, "The batch was cancelled"));
+    }
+
+    if is_streamed {
+      return Ok(Either::B(BatchSummary {
+        modules: res.results.len() as u32,
+        errors: res.results.values().filter(|res| res.result.is_err()).count() as u32,
+      }));
+    }
+
+    Ok(Either::A(res.results.into_values().map(get_batch_module_result).collect()))
   }
 }
 
-pub fn use_project(definition: ProjectDefinition) -> esfuse::Project {
+pub fn use_project(definition: ProjectDefinition) -> Result<esfuse::Project> {
   let mut project = esfuse::Project::new(&definition.root);
   for (ns, path_string) in &definition.namespaces {
     project.register_ns(ns, path_string);
   }
 
-  for hook in definition.on_resolve {
-    let tsfn: ThreadsafeFunction<esfuse::types::OnResolveArgs, ErrorStrategy::Fatal> = hook.cb
-      .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
-      .unwrap();
-
-    project.on_resolve.push(esfuse::types::PluginHook {
-      regexp: esfuse::utils::Regex::from_str(&hook.regexp).unwrap(),
-      params: Default::default(),
+  project.hook_timeout = definition.hook_timeout
+    .map(|ms| Duration::from_millis(ms as u64));
 
-      cb: |hook_data, args| {
-        Box::pin(async move {
-          let issuer = args.issuer.clone();
-          let span = args.span.clone();
+  register_js_hooks(&mut project, definition.on_resolve, "onResolve", |ctx| {
+    let (args, next_id) = ctx.value;
+    Ok(vec![Either::A(args), Either::B(next_id)])
+  }, JsHook::Resolve)?;
 
-          let user
-            = hook_data.downcast_ref::<HookData<esfuse::types::OnResolveArgs>>().unwrap().clone();
-          let future
-            = user.cb.call_async::<Promise<Option<ResolveResult>>>(args);
-
-          match future.await {
-            Ok(promise) => {
-              match promise.await {
-                Ok(hook_maybe) => hook_maybe.map(|hook_res| {
-                  esfuse::types::OnResolveResult {
-                    result: hook_res.value.ok_or_else(|| hook_res.error.unwrap()),
-                    dependencies: vec![],
-                  } 
-                }),
-  
-                Err(err) => Some(esfuse::types::OnResolveResult {
-                  result: Err(esfuse::CompilationError::from_string_with_highlight(err.to_string(), esfuse::utils::errors::Highlight {
-                    source: issuer.map(|locator| locator.url),
-                    subject: None,
-                    label: None,
-                    span: span.clone(),
-                  })),
-                  dependencies: vec![],
-                }),
-              }
-            },
-
-            Err(err) => Some(esfuse::types::OnResolveResult {
-              result: Err(esfuse::CompilationError::from_string_with_highlight(err.to_string(), esfuse::utils::errors::Highlight {
-                source: issuer.map(|locator| locator.url),
-                subject: None,
-                label: None,
-                span: span.clone(),
-              })),
-              dependencies: vec![],
-            }),
-          }
-        })
-      },
+  register_js_hooks(&mut project, definition.on_fetch, "onFetch", |ctx| {
+    let (args, next_id) = ctx.value;
+    Ok(vec![Either::A(args), Either::B(next_id)])
+  }, JsHook::Fetch)?;
 
-      data: Arc::new(Box::new(HookData::<esfuse::types::OnResolveArgs> {
-        cb: tsfn,
-      }))
-    });
-  }
+  register_js_hooks(&mut project, definition.on_transform, "onTransform", |ctx| Ok(vec![ctx.value]), JsHook::Transform)?;
+  register_js_hooks(&mut project, definition.on_render_chunk, "onRenderChunk", |ctx| Ok(vec![ctx.value]), JsHook::RenderChunk)?;
+  register_js_hooks(&mut project, definition.on_generate_bundle, "onGenerateBundle", |ctx| Ok(vec![ctx.value]), JsHook::GenerateBundle)?;
 
-  for hook in definition.on_fetch {
-    let tsfn: ThreadsafeFunction<esfuse::types::OnFetchArgs, ErrorStrategy::Fatal> = hook.cb
-      .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
-      .unwrap();
+  for (name, cb) in definition.transforms {
+    let hook_name
+      = format!("transforms.{}", name);
 
-    project.on_fetch.push(esfuse::types::PluginHook {
-      regexp: esfuse::utils::Regex::from_str(&hook.regexp).unwrap(),
-      params: Default::default(),
+    let tsfn: ThreadsafeFunction<esfuse::types::OnFetchResultData, ErrorStrategy::Fatal> = cb
+      .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
 
-      cb: |hook_data, args| {
+    project.register_query_transform(name, esfuse::types::QueryTransform {
+      cb: esfuse::types::QueryTransformHook::Fetch(|hook_data, args| {
         Box::pin(async move {
           let user
-            = hook_data.downcast_ref::<HookData<esfuse::types::OnFetchArgs>>().unwrap().clone();
-          let future
-            = user.cb.call_async::<Promise<Option<FetchResult>>>(args);
-
-          match future.await {
-            Ok(promise) => {
-              match promise.await {
-                Ok(hook_maybe) => hook_maybe.map(|hook_res| {
-                  esfuse::types::OnFetchResult {
-                    result: hook_res.value.ok_or_else(|| hook_res.error.unwrap()),
-                    dependencies: vec![],
-                  } 
-                }),
-  
-                Err(err) => Some(esfuse::types::OnFetchResult {
-                  result: Err(esfuse::CompilationError::from_napi(err)),
-                  dependencies: vec![],
-                }),
-              }
-            },
-
-            Err(err) => Some(esfuse::types::OnFetchResult {
-              result: Err(esfuse::CompilationError::from_napi(err)),
+            = hook_data.downcast_ref::<HookData<esfuse::types::OnFetchResultData>>().expect("Assertion failed: The hook data should match its hook type").clone();
+          let highlight
+            = get_hook_highlight(&user.name, Some(args.locator.url.clone()), None);
+
+          // Unlike the hooks, the transforms can't defer to the next one
+          call_js_hook::<_, FetchResult, _>(highlight.clone(), &user.cb, args).await.unwrap_or_else(|| {
+            esfuse::types::OnFetchResult {
+              result: Err(esfuse::CompilationError::from_str_with_highlight("The transform returned neither a value nor an error", highlight)),
               dependencies: vec![],
-            }),
-          }
+            }
+          })
         })
-      },
+      }),
 
-      data: Arc::new(Box::new(HookData::<esfuse::types::OnFetchArgs> {
+      data: Arc::new(Box::new(HookData::<esfuse::types::OnFetchResultData> {
+        name: hook_name,
         cb: tsfn,
-      }))
+      })),
+
+      mime_type: None,
+      needs_source: true,
+      bare_flag: false,
     });
   }
 
-  project
+  Ok(project)
 }
 
 #[napi(object)]
@@ -312,6 +676,13 @@ pub struct FetchResult {
   pub dependencies: Vec<esfuse::types::ModuleLocator>,
 }
 
+#[napi(object)]
+pub struct RenderChunkResult {
+  pub value: Option<esfuse::types::OnRenderChunkResultData>,
+  pub error: Option<esfuse::CompilationError>,
+  pub dependencies: Vec<esfuse::types::ModuleLocator>,
+}
+
 #[napi(object)]
 pub struct TransformResult {
   pub value: Option<esfuse::types::OnTransformResultData>,
@@ -326,6 +697,13 @@ pub struct BundleResult {
   pub dependencies: Vec<esfuse::types::ModuleLocator>,
 }
 
+#[napi(object)]
+pub struct BuildResult {
+  pub value: Option<esfuse::types::OnEmitResultData>,
+  pub error: Option<esfuse::CompilationError>,
+  pub dependencies: Vec<esfuse::types::ModuleLocator>,
+}
+
 #[napi(object)]
 pub struct BatchModuleResult {
   pub locator: esfuse::types::ModuleLocator,
@@ -334,10 +712,19 @@ pub struct BatchModuleResult {
   pub dependencies: Vec<esfuse::types::ModuleLocator>,
 }
 
+#[napi(object)]
+pub struct BatchSummary {
+  pub modules: u32,
+  pub errors: u32,
+}
+
 #[napi(object)]
 pub struct BatchModule {
   pub imaginary_path: Option<Path>,
   pub mime_type: String,
   pub code: String,
   pub map: Option<String>,
+  pub declaration: Option<String>,
+  pub declaration_error: Option<esfuse::CompilationError>,
+  pub integrity: String,
 }
@@ -1,7 +1,10 @@
 extern crate queues;
 
+use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::utils::GetLocatorVirtualPathOpts;
@@ -17,30 +20,109 @@ struct BatchMessage {
   sender: UnboundedSender<BatchMessage>,
 }
 
-pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResult {
+struct ProgressTracker {
+  on_progress: Option<OnBatchProgressHook>,
+  discovered: AtomicU32,
+  transformed: AtomicU32,
+  resolved: AtomicU32,
+}
+
+impl ProgressTracker {
+  fn new(on_progress: Option<OnBatchProgressHook>) -> Self {
+    Self {
+      on_progress,
+      discovered: AtomicU32::new(0),
+      transformed: AtomicU32::new(0),
+      resolved: AtomicU32::new(0),
+    }
+  }
+
+  fn report(&self, stage: BatchProgressStage, locator: &ModuleLocator) {
+    let counter = match stage {
+      BatchProgressStage::Discovered => &self.discovered,
+      BatchProgressStage::Transformed => &self.transformed,
+      BatchProgressStage::Resolved => &self.resolved,
+    };
+
+    counter.fetch_add(1, Ordering::SeqCst);
+
+    if let Some(on_progress) = &self.on_progress {
+      on_progress(OnBatchProgress {
+        stage,
+        locator: locator.clone(),
+        discovered: self.discovered.load(Ordering::SeqCst),
+        transformed: self.transformed.load(Ordering::SeqCst),
+        resolved: self.resolved.load(Ordering::SeqCst),
+      });
+    }
+  }
+}
+
+fn is_javascript_module(result: &OnBatchModuleResult) -> bool {
+  matches!(&result.result, Ok(batch_module) if batch_module.mime_type == "text/javascript")
+}
+
+// The JavaScript modules aren't reported yet when they still need to be
+// post-processed once the whole graph is known
+fn store_result(build_results: &Mutex<HashMap<String, OnBatchModuleResult>>, on_module: &Option<OnBatchModuleHook>, defer_javascript: bool, result: OnBatchModuleResult) {
+  if let Some(on_module) = on_module {
+    if !defer_javascript || !is_javascript_module(&result) {
+      on_module(&result);
+    }
+  }
+
+  build_results.lock().unwrap().insert(result.locator.url.clone(), result);
+}
+
+// Modules are deduplicated when queued rather than when scheduled, so that
+// the discovered count includes the ones still waiting for a slot
+fn enqueue(traversed: &Mutex<HashSet<ModuleLocator>>, progress: &ProgressTracker, sender: &UnboundedSender<BatchMessage>, locator: &ModuleLocator) {
+  if !traversed.lock().unwrap().insert(locator.clone()) {
+    return;
+  }
+
+  progress.report(BatchProgressStage::Discovered, locator);
+
+  // The receiver is only gone if the batch got cancelled, in which case
+  // we don't care about the dependencies anymore
+  let _ = sender.send(BatchMessage {
+    locator: locator.clone(),
+    sender: sender.clone(),
+  });
+}
+
+pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs, control: &BatchControl) -> OnBatchResult {
+  let cancellation
+    = &control.cancellation;
+  let progress
+    = Arc::new(ProgressTracker::new(control.on_progress.clone()));
+
   let build_results_container
     = Arc::new(Mutex::new(HashMap::new()));
 
   let (tx, mut rx)
     = tokio::sync::mpsc::unbounded_channel();
 
-  for locator in args.locators {
-    tx.send(BatchMessage {
-      locator: locator.clone(),
-      sender: tx.clone(),
-    }).unwrap();
+  let traversed
+    = Arc::new(Mutex::new(HashSet::new()));
+
+  for locator in &args.locators {
+    enqueue(&traversed, &progress, &tx, locator);
   }
 
   drop(tx);
 
-  let traversed = Arc::new(Mutex::new(HashSet::new()));
   let mut tasks = vec![];
 
   let transform_opts_base = Arc::new(OnTransformOpts {
     static_resolutions: Default::default(),
     swc: OnTransformSwcOpts {
+      emit_declarations: args.opts.emit_declarations,
+      module_format: args.opts.module_format,
       use_esfuse_runtime: args.opts.use_esfuse_runtime,
       promisify_body: false,
+      plugins: args.opts.swc_plugins.clone(),
+      builtins: args.opts.swc_builtins.clone(),
     },
     user_data: args.opts.user_data.clone(),
   });
@@ -50,12 +132,39 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
     user_data: args.opts.user_data.clone(),
   });
 
+  let module_format = args.opts.module_format;
+  let pin_resolutions = args.opts.pin_resolutions;
+  let hash_filenames = args.opts.hash_filenames;
+  let defer_javascript = pin_resolutions || hash_filenames;
+
+  // Modules are only scheduled once a slot is available, so the pending ones
+  // stay in the channel rather than as spawned tasks holding their sources
+  let concurrency = args.opts.concurrency
+    .map(|concurrency| concurrency.max(1) as usize)
+    .unwrap_or_else(get_default_concurrency);
+
+  let semaphore
+    = Arc::new(Semaphore::new(concurrency));
+
   let mut is_first_store = true;
 
-  while let Some(msg) = rx.recv().await {
-    if !traversed.lock().unwrap().insert(msg.locator.clone()) {
-      continue;
-    }
+  // The channel only closes once all tasks are done, since each of them
+  // holds a sender; cancelling stops the scheduling of new ones
+  loop {
+    let msg = tokio::select! {
+      msg = rx.recv() => msg,
+      _ = cancellation.cancelled() => None,
+    };
+
+    let msg = match msg {
+      Some(msg) => msg,
+      None => break,
+    };
+
+    let permit = tokio::select! {
+      permit = semaphore.clone().acquire_owned() => permit.expect("Assertion failed: The semaphore should never be closed"),
+      _ = cancellation.cancelled() => break,
+    };
 
     let is_first_iter = is_first_store;
     is_first_store = false;
@@ -75,9 +184,18 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
     let resolve_opts
       = resolve_opts_base.clone();
 
+    let task_progress
+      = progress.clone();
+    let task_traversed
+      = traversed.clone();
+    let task_on_module
+      = control.on_module.clone();
+
     let current_locator = msg.locator;
 
     let task = tokio::spawn(async move {
+      let _permit = permit;
+
       let mut transform_opts_iter
         = transform_opts.as_ref().clone();
 
@@ -90,11 +208,10 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
         opts: transform_opts_iter,
       }).await;
 
-      if let Err(transform_err) = transform_result.result {
-        let mut build_results
-          = build_results_accessor.lock().unwrap();
+      task_progress.report(BatchProgressStage::Transformed, &current_locator);
 
-        build_results.insert(current_locator.url.clone(), OnBatchModuleResult {
+      if let Err(transform_err) = transform_result.result {
+        store_result(&build_results_accessor, &task_on_module, defer_javascript, OnBatchModuleResult {
           locator: current_locator,
           result: Err(transform_err),
           dependencies: vec![],
@@ -109,20 +226,19 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
         resolutions,
         resolution_errors,
       ) = resolve_all(&project, current_locator.clone(), &transform.imports, &bundle_opts, &resolve_opts).await;
+
+      task_progress.report(BatchProgressStage::Resolved, &current_locator);
   
       for resolution in resolutions.values() {
         if let Some(resolution_locator) = resolution {
           if args.opts.traverse_dependencies {
-            msg.sender.send(BatchMessage {
-              locator: resolution_locator.clone(),
-              sender: msg.sender.clone(),
-            }).unwrap();
+            enqueue(&task_traversed, &task_progress, &msg.sender, resolution_locator);
           }
         }
       }
 
       if !resolution_errors.is_empty() {
-        build_results_accessor.lock().unwrap().insert(current_locator.url.clone(), OnBatchModuleResult {
+        store_result(&build_results_accessor, &task_on_module, defer_javascript, OnBatchModuleResult {
           locator: current_locator,
           result: Err(CompilationError {diagnostics: resolution_errors}),
           dependencies: vec![],
@@ -133,15 +249,45 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
 
       // Note: We do this before the lock(), to ensure
       // we're staying locked as little as possible
-      let mut batch_module =
-        OnBatchModule::new(transform, resolutions);
+      let mut batch_module = match OnBatchModule::new(transform, resolutions) {
+        Ok(batch_module) => batch_module,
+        Err(err) => {
+          store_result(&build_results_accessor, &task_on_module, defer_javascript, OnBatchModuleResult {
+            locator: current_locator,
+            result: Err(err),
+            dependencies: vec![],
+          });
+
+          return;
+        },
+      };
 
       batch_module.imaginary_path = utils::get_locator_virtual_path(&project, &current_locator, GetLocatorVirtualPathOpts {
         mime_type: &batch_module.mime_type,
         generated_module_folder: &generated_module_folder,
       });
+
+      // JavaScript modules depend on the final paths of their dependencies,
+      // so they're hashed once the whole graph is known (see below)
+      if bundle_opts.hash_filenames && batch_module.mime_type != "text/javascript" {
+        let content_hash = match batch_module.bytes() {
+          Ok(bytes) => utils::get_content_hash(bytes),
+          Err(err) => {
+            store_result(&build_results_accessor, &task_on_module, defer_javascript, OnBatchModuleResult {
+              locator: current_locator,
+              result: Err(err),
+              dependencies: vec![],
+            });
+
+            return;
+          },
+        };
+
+        batch_module.imaginary_path = batch_module.imaginary_path
+          .map(|p| utils::get_hashed_path(&p, &content_hash));
+      }
   
-      build_results_accessor.lock().unwrap().insert(current_locator.url.clone(), OnBatchModuleResult {
+      store_result(&build_results_accessor, &task_on_module, defer_javascript, OnBatchModuleResult {
         locator: current_locator,
         result: Ok(batch_module),
         dependencies: vec![],
@@ -151,6 +297,17 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
     tasks.push(task);
   }
 
+  if cancellation.is_cancelled() {
+    for task in &tasks {
+      task.abort();
+    }
+
+    return OnBatchResult {
+      results: Default::default(),
+      cancelled: true,
+    };
+  }
+
   // Wait for all tasks to finish
   for task in tasks {
     task.await.unwrap();
@@ -159,43 +316,137 @@ pub async fn batch(project_base: Arc<Project>, args: OnBatchArgs) -> OnBatchResu
   let mut results
     = Arc::try_unwrap(build_results_container).unwrap().into_inner().unwrap();
 
+  if hash_filenames {
+    hash_javascript_paths(&mut results);
+  }
+
+  if !pin_resolutions {
+    // The JavaScript modules held back by the tasks now have their final path
+    if let Some(on_module) = control.on_module.as_ref().filter(|_| defer_javascript) {
+      for result in results.values().filter(|result| is_javascript_module(result)) {
+        on_module(result);
+      }
+    }
+
+    if let Some(on_mappings) = &control.on_mappings {
+      on_mappings(&compute_resolution_mappings(&results));
+    }
+
+    return OnBatchResult {
+      results,
+      cancelled: false,
+    };
+  }
+
   let all_resolution_mappings
-    = compute_resolution_mappings(&results);
+    = Arc::new(compute_resolution_mappings(&results));
 
-  for (_, result) in results.iter_mut() {
-    if let Ok(batch_module) = &mut result.result {
-      let resolution_mappings
-        = all_resolution_mappings
-          .get(&result.locator.url)
-          .unwrap();
+  // Pinning parses and prints the modules again, so it runs on the blocking
+  // pool; each module is reported as soon as it's been pinned
+  let mut pinned_results = HashMap::with_capacity(results.len());
+  let mut pin_tasks = vec![];
 
-      if batch_module.mime_type == "text/javascript" {
-        let source_map
-          = batch_module.map.as_mut().unwrap().to_json(None).unwrap();
+  for (url, mut result) in results {
+    if !is_javascript_module(&result) {
+      pinned_results.insert(url, result);
+      continue;
+    }
 
-        let dependency_postprocessing = utils::swc::persist_resolutions(
-          &result.locator,
-          &batch_module.code,
-          &source_map,
-          resolution_mappings,
-        );
+    let resolution_mappings
+      = all_resolution_mappings.clone();
+    let task_cancellation
+      = cancellation.clone();
+    let task_on_module
+      = control.on_module.clone();
 
-        if let Ok(dependency_postprocessing) = dependency_postprocessing {
-          batch_module.set_code(dependency_postprocessing.code);
-          batch_module.set_map(dependency_postprocessing.map);
+    pin_tasks.push(tokio::task::spawn_blocking(move || {
+      if task_cancellation.is_cancelled() {
+        return (url, result);
+      }
 
-          println!("Persisted resolutions for {}", &result.locator.url);
-        }
+      let pin_res = match &mut result.result {
+        Ok(batch_module) => {
+          let module_mappings
+            = resolution_mappings
+              .get(&url)
+              .unwrap();
+
+          pin_module(&result.locator, batch_module, module_mappings, module_format)
+        },
+
+        Err(_) => Ok(()),
+      };
+
+      // The unpinned code would still import its dependencies by their
+      // original requests, so the module is reported as failed instead
+      if let Err(err) = pin_res {
+        result.result = Err(err);
       }
-    }
+
+      if let Some(on_module) = &task_on_module {
+        on_module(&result);
+      }
+
+      (url, result)
+    }));
+  }
+
+  for task in pin_tasks {
+    let (url, result)
+      = task.await.unwrap();
+
+    pinned_results.insert(url, result);
+  }
+
+  if cancellation.is_cancelled() {
+    return OnBatchResult {
+      results: Default::default(),
+      cancelled: true,
+    };
+  }
+
+  if let Some(on_mappings) = &control.on_mappings {
+    on_mappings(&all_resolution_mappings);
   }
 
   OnBatchResult {
-    results,
+    results: pinned_results,
+    cancelled: false,
   }
 }
 
-async fn resolve_all(project: &Project, locator: ModuleLocator, imports: &[Import], bundle_opts: &OnBatchOpts, resolve_opts: &OnResolveOpts) -> (HashMap<String, Option<ModuleLocator>>, Vec<Diagnostic>) {
+// Modules returned by the transform hooks may not have a source map, in
+// which case the pinned code is only mapped to the code they returned
+fn pin_module(locator: &ModuleLocator, batch_module: &mut OnBatchModule, resolution_mappings: &HashMap<String, String>, module_format: ModuleFormat) -> Result<(), CompilationError> {
+  let source_map = batch_module.map.as_mut().map(|map| {
+    map.to_json(None).map_err(|err| {
+      CompilationError::from_string(format!("Failed to serialize the source map ({})", err))
+    })
+  }).transpose()?;
+
+  let dependency_postprocessing = utils::swc::persist_resolutions(
+    locator,
+    &batch_module.code,
+    source_map.as_deref(),
+    resolution_mappings,
+    module_format,
+  )?;
+
+  batch_module.set_code(dependency_postprocessing.code);
+  batch_module.set_map(dependency_postprocessing.map)?;
+
+  Ok(())
+}
+
+// Most of the time is spent either in the transforms (which run on the
+// blocking pool) or waiting on hooks, so we allow more tasks than cores
+fn get_default_concurrency() -> usize {
+  std::thread::available_parallelism()
+    .map(|parallelism| parallelism.get() * 4)
+    .unwrap_or(16)
+}
+
+async fn resolve_all(project: &Arc<Project>, locator: ModuleLocator, imports: &[Import], bundle_opts: &OnBatchOpts, resolve_opts: &OnResolveOpts) -> (HashMap<String, Option<ModuleLocator>>, Vec<Diagnostic>) {
   let mut resolutions = HashMap::new();
   let mut resolution_errors = Vec::new();
 
@@ -203,7 +454,7 @@ async fn resolve_all(project: &Project, locator: ModuleLocator, imports: &[Impor
     = project.package_dir_from_locator(&locator);
 
   for import in imports {
-    let mut resolution = super::resolve::resolve(&project, OnResolveArgs {
+    let mut resolution = super::resolve::resolve(project, OnResolveArgs {
       kind: import.kind,
       request: import.specifier.clone(),
       issuer: Some(locator.clone()),
@@ -252,6 +503,149 @@ async fn resolve_all(project: &Project, locator: ModuleLocator, imports: &[Impor
   (resolutions, resolution_errors)
 }
 
+// Iterative version of Tarjan's algorithm; components are returned in
+// reverse topological order, so each of them comes after its dependencies
+fn get_strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+  let mut indices = vec![usize::MAX; edges.len()];
+  let mut lowlinks = vec![0; edges.len()];
+  let mut on_stack = vec![false; edges.len()];
+
+  let mut stack = vec![];
+  let mut components = vec![];
+  let mut counter = 0;
+
+  for root in 0..edges.len() {
+    if indices[root] != usize::MAX {
+      continue;
+    }
+
+    indices[root] = counter;
+    lowlinks[root] = counter;
+    counter += 1;
+
+    stack.push(root);
+    on_stack[root] = true;
+
+    let mut call_stack = vec![(root, 0)];
+
+    while let Some((node, edge_index)) = call_stack.last_mut() {
+      let node = *node;
+
+      if let Some(&next) = edges[node].get(*edge_index) {
+        *edge_index += 1;
+
+        if indices[next] == usize::MAX {
+          indices[next] = counter;
+          lowlinks[next] = counter;
+          counter += 1;
+
+          stack.push(next);
+          on_stack[next] = true;
+
+          call_stack.push((next, 0));
+        } else if on_stack[next] {
+          lowlinks[node] = lowlinks[node].min(indices[next]);
+        }
+
+        continue;
+      }
+
+      call_stack.pop();
+
+      if let Some((parent, _)) = call_stack.last() {
+        lowlinks[*parent] = lowlinks[*parent].min(lowlinks[node]);
+      }
+
+      if lowlinks[node] == indices[node] {
+        let mut component = vec![];
+
+        loop {
+          let member = stack.pop().unwrap();
+          on_stack[member] = false;
+          component.push(member);
+
+          if member == node {
+            break;
+          }
+        }
+
+        components.push(component);
+      }
+    }
+  }
+
+  components
+}
+
+// The final code of a JavaScript module embeds the paths of its dependencies,
+// so its hash must cover everything it can reach. Modules within a cycle
+// can't be ordered, so they share the hash of their whole component.
+fn hash_javascript_paths(results: &mut HashMap<String, OnBatchModuleResult>) {
+  let mut urls: Vec<String> = results.iter()
+    .filter(|(_, result)| result.result.is_ok())
+    .map(|(url, _)| url.clone())
+    .collect();
+
+  urls.sort();
+
+  let url_indices: HashMap<&str, usize>
+    = urls.iter().enumerate().map(|(index, url)| (url.as_str(), index)).collect();
+
+  let modules: Vec<&OnBatchModule> = urls.iter().map(|url| {
+    results[url].result.as_ref().unwrap()
+  }).collect();
+
+  let edges: Vec<Vec<usize>> = modules.iter().map(|module| {
+    module.resolutions.values()
+      .flatten()
+      .filter_map(|locator| url_indices.get(locator.url.as_str()).copied())
+      .sorted()
+      .dedup()
+      .collect()
+  }).collect();
+
+  let components
+    = get_strongly_connected_components(&edges);
+
+  let mut module_components = vec![0; urls.len()];
+  for (component_index, component) in components.iter().enumerate() {
+    for &module_index in component {
+      module_components[module_index] = component_index;
+    }
+  }
+
+  let mut component_hashes: Vec<String> = vec![];
+
+  for (component_index, component) in components.iter().enumerate() {
+    let mut parts: Vec<String> = component.iter().map(|&module_index| {
+      utils::get_content_hash(&modules[module_index].code)
+    }).collect();
+
+    parts.extend(component.iter()
+      .flat_map(|&module_index| &edges[module_index])
+      .map(|&dependency_index| module_components[dependency_index])
+      .filter(|&dependency_component| dependency_component != component_index)
+      .map(|dependency_component| component_hashes[dependency_component].clone()));
+
+    parts.sort();
+    parts.dedup();
+
+    component_hashes.push(utils::get_content_hash(parts.join("\n")));
+  }
+
+  for (module_index, url) in urls.iter().enumerate() {
+    if let Ok(batch_module) = &mut results.get_mut(url).unwrap().result {
+      if batch_module.mime_type == "text/javascript" {
+        let content_hash
+          = &component_hashes[module_components[module_index]];
+
+        batch_module.imaginary_path = batch_module.imaginary_path.as_ref()
+          .map(|p| utils::get_hashed_path(p, content_hash));
+      }
+    }
+  }
+}
+
 fn compute_resolution_mappings(modules: &HashMap<String, OnBatchModuleResult>) -> HashMap<String, HashMap<String, String>> {
   let mut all_module_mappings = HashMap::new();
 
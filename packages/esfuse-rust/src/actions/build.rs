@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::{CompilationError, Project};
+use crate::types::*;
+
+use super::batch::batch;
+use super::emit::{emit, emit_stream};
+
+pub async fn build(project: Arc<Project>, args: OnBuildArgs, control: &BatchControl) -> OnEmitResult {
+  let mut batch_opts = args.opts.batch;
+
+  // The resolutions are persisted during the emit phase, since they must
+  // point to the paths the modules are written to rather than to their
+  // source paths
+  batch_opts.pin_resolutions = false;
+
+  // Variants are generated from ES modules, with their resolutions
+  // persisted separately for each of them
+  if !args.opts.emit.variants.is_empty() {
+    batch_opts.module_format = ModuleFormat::Esm;
+  }
+
+  let module_format
+    = batch_opts.module_format;
+
+  let (sender, receiver)
+    = tokio::sync::mpsc::unbounded_channel();
+
+  let on_module
+    = control.on_module.clone();
+
+  // The modules are written as the batch reports them; the channel closes
+  // once the batch is done and drops its control
+  let batch_control = BatchControl {
+    on_module: Some(Arc::new(move |result: &OnBatchModuleResult| {
+      if let Some(on_module) = &on_module {
+        on_module(result);
+      }
+
+      let _ = sender.send(result.clone());
+    })),
+    ..control.clone()
+  };
+
+  let emit_opts
+    = args.opts.emit;
+  let batch_project
+    = project.clone();
+
+  let (batch_result, streamed) = tokio::join!(
+    async move {
+      let batch_result = batch(batch_project, OnBatchArgs {
+        locators: args.locators,
+        opts: batch_opts,
+      }, &batch_control).await;
+
+      drop(batch_control);
+      batch_result
+    },
+    emit_stream(&emit_opts, module_format, receiver),
+  );
+
+  if batch_result.cancelled {
+    streamed.discard().await;
+
+    return OnEmitResult {
+      result: Err(CompilationError::from_str("The build was cancelled")),
+      dependencies: vec![],
+    };
+  }
+
+  emit(&project, OnEmitArgs {
+    batch: batch_result,
+    module_format,
+    opts: emit_opts,
+  }, streamed).await
+}
@@ -1,33 +1,197 @@
 extern crate queues;
 
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
+use crate::classes::plugin::{applies_to, run_hook};
 use crate::utils;
-use crate::Project;
+use crate::{CompilationError, Project};
 use crate::types::*;
 
 use super::batch::batch;
 
-pub async fn bundle(project_base: Arc<Project>, args: OnBundleArgs) -> OnBundleResult {
+struct BundleChunk {
+  nl_count: usize,
+  source: String,
+  source_map: parcel_sourcemap::SourceMap,
+  meta: HashMap<String, OnBundleModuleMeta>,
+}
+
+impl BundleChunk {
+  fn new(runtime: Option<&str>) -> Self {
+    let mut chunk = Self {
+      nl_count: 0,
+      source: String::default(),
+      source_map: parcel_sourcemap::SourceMap::new(""),
+      meta: HashMap::new(),
+    };
+
+    // The runtime is idempotent, so injecting it in each chunk lets
+    // them be evaluated in any order
+    if let Some(runtime) = runtime {
+      chunk.nl_count += count_newlines(runtime);
+      chunk.source.push_str(runtime);
+      chunk.source.push(' ');
+    }
+
+    chunk
+  }
+
+  fn push_module(&mut self, project: &Project, url: String, result: OnBatchModuleResult) {
+    if self.nl_count > 0 {
+      self.nl_count += 1;
+      self.source += "\n";
+    }
+
+    match result.result {
+      Ok(module) => {
+        if let Some(mut map) = module.map {
+          self.source_map.add_sourcemap(&mut map, self.nl_count as i64)
+            .expect("Assertion failed: Expected the SWC-generated sourcemap to be well-structured");
+        }
+
+        self.nl_count += module.newlines;
+        self.source += module.code.as_str();
+
+        self.meta.insert(url, OnBundleModuleMeta {
+          error: None,
+          path: result.locator.physical_path(project),
+          resolutions: HashMap::from_iter(module.resolutions.into_iter().map(|(k, v)| {
+            (k, v.map(|l| l.url))
+          }).collect_vec()),
+        });
+      },
+
+      Err(err) => {
+        self.meta.insert(url, OnBundleModuleMeta {
+          error: Some(err),
+          path: None,
+          resolutions: Default::default(),
+        });
+      },
+    };
+  }
+
+  fn push_meta(&mut self, subject: &String) {
+    if self.nl_count > 0 {
+      self.source += "\n";
+    }
+
+    self.source += format!("$esfuse$.meta({});\n", utils::serialize_json(&self.meta, subject).unwrap()).as_str();
+  }
+
+  async fn render(&mut self, project: &Project, entry: &str, name: Option<&str>) -> Result<Vec<ModuleLocator>, CompilationError> {
+    let mut dependencies = vec![];
+
+    for plugin in &project.plugins {
+      if !applies_to(plugin.as_ref(), PluginHookKind::RenderChunk, entry) {
+        continue;
+      }
+
+      let hook_res = run_hook(project, plugin.as_ref(), entry, plugin.clone().render_chunk(OnRenderChunkArgs {
+        entry: entry.to_string(),
+        chunk: name.map(|name| name.to_string()),
+        code: self.source.clone(),
+        map: self.source_map.to_json(None).expect("Should have been able to serialize the source map"),
+        meta: self.meta.clone(),
+      })).await;
+
+      if let Some(mut hook_res) = hook_res {
+        dependencies.append(&mut hook_res.dependencies);
+
+        let hook_data
+          = hook_res.result?;
+
+        // The hook map describes how the code it received got turned into
+        // the code it returned; extending it with the chunk map lets us
+        // trace the final code back to the original modules
+        if let Some(hook_map) = hook_data.map {
+          let mut source_map = parcel_sourcemap::SourceMap::from_json("", &hook_map).map_err(|err| {
+            CompilationError::from_string(format!("Invalid source map returned by a render hook ({})", err))
+          })?;
+
+          source_map.extends(&mut self.source_map).map_err(|err| {
+            CompilationError::from_string(format!("Failed to compose the source map returned by a render hook ({})", err))
+          })?;
+
+          self.source_map = source_map;
+        }
+
+        self.nl_count = count_newlines(&hook_data.code);
+        self.source = hook_data.code;
+      }
+    }
+
+    Ok(dependencies)
+  }
+}
+
+async fn generate_bundle(project: &Project, bundle: OnBundleResultData) -> OnBundleResult {
+  let mut bundle = bundle;
+  let mut dependencies = vec![];
+
+  for plugin in &project.plugins {
+    if !applies_to(plugin.as_ref(), PluginHookKind::GenerateBundle, &bundle.entry) {
+      continue;
+    }
+
+    let hook_res = run_hook(project, plugin.as_ref(), &bundle.entry, plugin.clone().generate_bundle(OnGenerateBundleArgs {
+      bundle: bundle.clone(),
+    })).await;
+
+    if let Some(mut hook_res) = hook_res {
+      dependencies.append(&mut hook_res.dependencies);
+
+      match hook_res.result.and_then(|hook_bundle| check_chunks_unchanged(&bundle, hook_bundle)) {
+        Ok(hook_bundle) => {
+          bundle = hook_bundle;
+        },
+
+        Err(err) => {
+          return OnBundleResult {
+            result: Err(err),
+            dependencies,
+          };
+        },
+      }
+    }
+  }
+
+  OnBundleResult {
+    result: Ok(bundle),
+    dependencies,
+  }
+}
+
+// The entry embeds the filenames and integrities of the named chunks, so
+// the generate hooks can't change their code without breaking the entry;
+// the render hooks are the ones meant to do that
+fn check_chunks_unchanged(bundle: &OnBundleResultData, hook_bundle: OnBundleResultData) -> Result<OnBundleResultData, CompilationError> {
+  let is_unchanged = bundle.chunks.len() == hook_bundle.chunks.len() && bundle.chunks.iter().zip(&hook_bundle.chunks).all(|(chunk, hook_chunk)| {
+    chunk.name == hook_chunk.name && chunk.filename == hook_chunk.filename && chunk.integrity == utils::get_integrity(&hook_chunk.code)
+  });
+
+  match is_unchanged {
+    true => Ok(hook_bundle),
+    false => Err(CompilationError::from_string(format!("A generate bundle hook changed the named chunks of {} (use a render chunk hook instead)", bundle.entry))),
+  }
+}
+
+pub async fn bundle(project_base: Arc<Project>, args: OnBundleArgs, control: &BatchControl) -> OnBundleResult {
   let project = project_base.as_ref();
 
-  let mut final_nl_count = 0_usize;
-  let mut final_source = String::default();
-  let mut final_source_map = parcel_sourcemap::SourceMap::new("");
+  let mut runtime_code = None;
 
   if let Some(runtime_locator) = &args.opts.runtime {
-    let runtime_res = super::transform::transform(project, OnTransformArgs {
+    let runtime_res = super::transform::transform(&project_base, OnTransformArgs {
       locator: runtime_locator.clone(),
       opts: Default::default(),
     }).await;
 
     match runtime_res.result {
       Ok(runtime) => {
-        final_nl_count += count_newlines(&runtime.code);
-        final_source.push_str(&runtime.code);
-        final_source.push(' ');
+        runtime_code = Some(runtime.code);
       },
 
       Err(err) => {
@@ -42,7 +206,14 @@ pub async fn bundle(project_base: Arc<Project>, args: OnBundleArgs) -> OnBundleR
   let mut build_results = batch(project_base.clone(), OnBatchArgs {
     locators: vec![args.locator.clone()],
     opts: args.opts.batch,
-  }).await;
+  }, control).await;
+
+  if build_results.cancelled {
+    return OnBundleResult {
+      result: Err(CompilationError::from_str("The bundle was cancelled")),
+      dependencies: vec![],
+    };
+  }
 
   let mut sorted_results: Vec<(String, OnBatchModuleResult)>
     = build_results.results.drain().collect();
@@ -51,69 +222,136 @@ pub async fn bundle(project_base: Arc<Project>, args: OnBundleArgs) -> OnBundleR
     b.0.cmp(&a.0)
   });
 
-  let mut meta = HashMap::new();
+  let mut entry_chunk
+    = BundleChunk::new(runtime_code.as_deref());
+  let mut named_chunks: BTreeMap<String, BundleChunk>
+    = BTreeMap::new();
 
   for (url, result) in sorted_results {
-    if final_nl_count > 0 {
-      final_nl_count += 1;
-      final_source += "\n";
-    }
-  
-    match result.result {
-      Ok(module) => {
-        if let Some(mut map) = module.map {
-          final_source_map.add_sourcemap(&mut map, final_nl_count as i64)
-            .expect("Assertion failed: Expected the SWC-generated sourcemap to be well-structured");  
-        }
+    let chunk_name = match url == args.locator.url {
+      true => None,
+      false => utils::get_manual_chunk(project, &result.locator, &args.opts.chunks),
+    };
 
-        final_nl_count += module.newlines;
-        final_source += module.code.as_str();
+    let chunk = match chunk_name {
+      Some(name) => named_chunks.entry(name.to_string()).or_insert_with(|| {
+        BundleChunk::new(runtime_code.as_deref())
+      }),
 
-        meta.insert(url, OnBundleModuleMeta {
-          error: None,
-          path: result.locator.physical_path(project),
-          resolutions: HashMap::from_iter(module.resolutions.into_iter().map(|(k, v)| {
-            (k, v.map(|l| l.url))
-          }).collect_vec()),
-        });
-      },
+      None => &mut entry_chunk,
+    };
 
-      Err(err) => {
-        meta.insert(url, OnBundleModuleMeta {
-          error: Some(err),
-          path: None,
-          resolutions: Default::default(),
-        });
+    chunk.push_module(project, url, result);
+  }
+
+  let mut dependencies = vec![];
+  let mut chunks = vec![];
+
+  // Named chunks are rendered first, since the entry needs to know their
+  // final filenames to be able to load them
+  for (name, mut chunk) in named_chunks {
+    chunk.push_meta(&args.locator.url);
+    chunk.source += format!("$esfuse$.chunkLoaded({});\n", utils::serialize_json(&name, &args.locator.url).unwrap()).as_str();
+
+    match chunk.render(project, &args.locator.url, Some(&name)).await {
+      Ok(mut render_dependencies) => dependencies.append(&mut render_dependencies),
+      Err(err) => return OnBundleResult {
+        result: Err(err),
+        dependencies,
       },
-    };
+    }
+
+    let filename
+      = format!("{}.{}.js", name, utils::get_content_hash(&chunk.source));
+
+    // The chunks are served from the same folder as their maps
+    chunk.source += format!("\n//# sourceMappingURL={}.map\n", filename).as_str();
+
+    let integrity
+      = utils::get_integrity(&chunk.source);
+
+    chunks.push(OnBundleChunk {
+      name,
+      filename,
+      integrity,
+      code: chunk.source,
+      map: chunk.source_map.to_json(None).expect("Should have been able to serialize the source map"),
+    });
   }
 
-  if final_nl_count > 0 {
-    final_source += "\n";
+  entry_chunk.push_meta(&args.locator.url);
+
+  if !chunks.is_empty() {
+    let public_path
+      = args.opts.public_path.as_deref().unwrap_or_default();
+
+    let chunk_manifest: BTreeMap<&str, serde_json::Value> = chunks.iter().map(|chunk| {
+      (chunk.name.as_str(), serde_json::json!({
+        "url": format!("{}{}", public_path, chunk.filename),
+        "integrity": chunk.integrity,
+      }))
+    }).collect();
+
+    entry_chunk.source += format!("$esfuse$.registerChunks({});\n", utils::serialize_json(&chunk_manifest, &args.locator.url).unwrap()).as_str();
   }
-  
-  final_source += format!("$esfuse$.meta({});\n", utils::serialize_json(&meta, &args.locator.url).unwrap()).as_str();
 
   if args.opts.require_on_load {
-    final_source += format!("\n(typeof module !== 'undefined' ? module : {{}}).exports = $esfuse$.require({});\n", utils::serialize_json(&args.locator.url, &args.locator.url).unwrap()).as_str();
+    let entry_json
+      = utils::serialize_json(&args.locator.url, &args.locator.url).unwrap();
+
+    // Modules moved into named chunks can only be required once those
+    // chunks have been evaluated, so the entry waits for them
+    entry_chunk.source += match chunks.is_empty() {
+      true => format!("\n(typeof module !== 'undefined' ? module : {{}}).exports = $esfuse$.require({});\n", entry_json),
+      false => format!("\n(typeof module !== 'undefined' ? module : {{}}).exports = $esfuse$.loadChunks().then(() => $esfuse$.require({}));\n", entry_json),
+    };
   }
 
-  final_source += format!("\n//# sourceMappingURL={}\n", ModuleLocator::new(
+  match entry_chunk.render(project, &args.locator.url, None).await {
+    Ok(mut render_dependencies) => dependencies.append(&mut render_dependencies),
+    Err(err) => return OnBundleResult {
+      result: Err(err),
+      dependencies,
+    },
+  }
+
+  entry_chunk.source += format!("\n//# sourceMappingURL={}\n", ModuleLocator::new(
     args.locator.kind,
     format!("{}.map", &args.locator.specifier),
     args.locator.params,
   ).url).as_str();
 
+  let entry_basename = args.locator.specifier
+    .rsplit('/').next().unwrap_or_default();
+  let entry_stem
+    = &entry_basename[0..entry_basename.len() - utils::get_extension(entry_basename).len()];
+
+  let mut bundle_res = generate_bundle(project, OnBundleResultData {
+    entry: args.locator.url,
+    mime_type: String::from("text/javascript"),
+
+    filename: String::new(),
+    integrity: String::new(),
+
+    code: entry_chunk.source,
+    map: entry_chunk.source_map.to_json(None).expect("Should have been able to serialize the source map"),
+
+    chunks,
+    files: vec![],
+  }).await;
+
+  dependencies.append(&mut bundle_res.dependencies);
+
+  // The entry is only named once the hooks are done, since they may
+  // have changed its code
+  if let Ok(bundle) = &mut bundle_res.result {
+    bundle.filename = format!("{}.{}.js", entry_stem, utils::get_content_hash(&bundle.code));
+    bundle.integrity = utils::get_integrity(&bundle.code);
+  }
+
   OnBundleResult {
-    result: Ok(OnBundleResultData {
-      entry: args.locator.url,
-      mime_type: String::from("text/javascript"),
-
-      code: final_source,
-      map: final_source_map.to_json(None).expect("Should have been able to serialize the source map"),
-    }),
-    dependencies: vec![
-    ],
+    result: bundle_res.result,
+    dependencies,
   }
 }
 
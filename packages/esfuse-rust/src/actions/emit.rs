@@ -0,0 +1,531 @@
+use arca::{Path, ToArcaPath};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::types::*;
+use crate::utils;
+use crate::utils::errors::Diagnostic;
+use crate::utils::package_json::PackageJsonRewriter;
+use crate::{CompilationError, Project};
+
+#[derive(Serialize)]
+struct ManifestEntry {
+  file: String,
+  integrity: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  format: Option<&'static str>,
+}
+
+async fn write_file(path: Path, content: Vec<u8>) -> Result<(), Diagnostic> {
+  let path_buf = path.to_path_buf();
+
+  if let Some(parent) = path_buf.parent() {
+    tokio::fs::create_dir_all(parent).await.map_err(|err| {
+      Diagnostic::from_string(format!("Failed to create the {} directory ({})", parent.display(), err))
+    })?;
+  }
+
+  tokio::fs::write(&path_buf, content).await.map_err(|err| {
+    Diagnostic::from_string(format!("Failed to write {} ({})", path, err))
+  })
+}
+
+async fn rewrite_package_json(opts: &OnEmitOpts, files: &[OnEmitFile]) -> Result<Vec<u8>, CompilationError> {
+  let source_path
+    = opts.source_folder.join(&Path::from("package.json"));
+
+  let source = tokio::fs::read_to_string(source_path.to_path_buf()).await.map_err(|err| {
+    CompilationError::from_string(format!("Failed to read {} ({})", source_path, err))
+  })?;
+
+  let pkg: serde_json::Value = serde_json::from_str(&source).map_err(|err| {
+    CompilationError::from_json(&err, source_path.to_string())
+  })?;
+
+  let rewriter
+    = PackageJsonRewriter::new(&opts.source_folder, &opts.dist_folder, files);
+
+  Ok((serde_json::to_string_pretty(&rewriter.rewrite(&pkg)).unwrap() + "\n").into_bytes())
+}
+
+async fn find_copied_files(opts: &OnEmitOpts) -> Result<Vec<Path>, Diagnostic> {
+  let mut files = vec![];
+
+  if opts.copy.is_empty() {
+    return Ok(files);
+  }
+
+  let dist_folder
+    = opts.dist_folder.to_path_buf();
+
+  let mut queue
+    = vec![opts.source_folder.to_path_buf()];
+
+  let read_err = |dir: &std::path::PathBuf, err: std::io::Error| {
+    Diagnostic::from_string(format!("Failed to read the {} directory ({})", dir.display(), err))
+  };
+
+  while let Some(dir) = queue.pop() {
+    let mut entries = tokio::fs::read_dir(&dir).await
+      .map_err(|err| read_err(&dir, err))?;
+
+    while let Some(entry) = entries.next_entry().await.map_err(|err| read_err(&dir, err))? {
+      let entry_path = entry.path();
+
+      let file_type = entry.file_type().await
+        .map_err(|err| read_err(&dir, err))?;
+
+      if file_type.is_dir() {
+        if entry_path != dist_folder && entry.file_name() != "node_modules" && entry.file_name() != ".git" {
+          queue.push(entry_path);
+        }
+
+        continue;
+      }
+
+      let path = entry_path.to_arca();
+      let relative_path = path.relative_to(&opts.source_folder);
+
+      if opts.copy.iter().any(|pattern| glob_match::glob_match(pattern.trim_start_matches("./"), relative_path.as_str())) {
+        files.push(path);
+      }
+    }
+  }
+
+  files.sort_by(|a, b| {
+    a.as_str().cmp(b.as_str())
+  });
+
+  Ok(files)
+}
+
+fn get_variant_indices(opts: &OnEmitOpts, module: &OnBatchModule) -> Vec<Option<usize>> {
+  match opts.variants.is_empty() || module.mime_type != "text/javascript" {
+    true => vec![None],
+    false => (0..opts.variants.len()).map(Some).collect(),
+  }
+}
+
+fn get_dist_path(opts: &OnEmitOpts, locator: &ModuleLocator, module: &OnBatchModule, variant_index: Option<usize>) -> Result<Path, Diagnostic> {
+  let source_path = module.imaginary_path.as_ref().ok_or_else(|| {
+    Diagnostic::from_string(format!("Cannot emit {} (no imaginary path)", locator.url))
+  })?;
+
+  let relative_path
+    = source_path.relative_to(&opts.source_folder);
+
+  if relative_path.as_str().starts_with("../") {
+    return Err(Diagnostic::from_string(format!("Cannot emit {} (outside of the source folder)", locator.url)));
+  }
+
+  let path
+    = opts.dist_folder.join(&relative_path);
+
+  let ext = match variant_index {
+    Some(variant_index) => Some(&opts.variants[variant_index].extension),
+    None => opts.extensions.get(&module.mime_type),
+  };
+
+  Ok(match ext {
+    Some(ext) => utils::replace_extension(&path, ext),
+    None => path,
+  })
+}
+
+#[derive(Default)]
+struct EmitOutput {
+  files: Vec<OnEmitFile>,
+  writes: Vec<(Path, Vec<u8>)>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+// Each import is rewritten to point to the file its dependency is written
+// to, preferably the one of the same variant
+fn get_dependency_mappings(module: &OnBatchModule, path: &Path, dist_paths: &HashMap<(String, Option<usize>), Path>, variant_index: Option<usize>) -> HashMap<String, String> {
+  module.resolutions.iter().filter_map(|(request, resolution)| {
+    let dependency_url = &resolution.as_ref()?.url;
+
+    let dependency_path = dist_paths.get(&(dependency_url.clone(), variant_index))
+      .or_else(|| dist_paths.get(&(dependency_url.clone(), None)))?;
+
+    Some((request.clone(), utils::get_relative_specifier(path, dependency_path)))
+  }).collect()
+}
+
+// JavaScript modules need the dist paths of their dependencies, the other
+// outputs only need the module's own
+fn render_module(opts: &OnEmitOpts, module_format: ModuleFormat, locator: &ModuleLocator, mut module: OnBatchModule, dist_paths: &HashMap<(String, Option<usize>), Path>, output: &mut EmitOutput) {
+  let source_path = module.imaginary_path.clone()
+    .expect("Assertion failed: The imaginary path should have been checked when computing the dist paths");
+
+  if let Some(mut err) = module.declaration_error.take() {
+    output.diagnostics.append(&mut err.diagnostics);
+  }
+
+  let declaration
+    = module.declaration.take();
+
+  for variant_index in get_variant_indices(opts, &module) {
+    let path = dist_paths.get(&(locator.url.clone(), variant_index))
+      .expect("Assertion failed: The dist path should have been computed before rendering the module")
+      .clone();
+
+    let source_map = module.map.as_mut().map(|map| {
+      map.to_json(None).expect("Should have been able to serialize the source map")
+    });
+
+    let mappings = match module.mime_type == "text/javascript" {
+      true => get_dependency_mappings(&module, &path, dist_paths, variant_index),
+      false => HashMap::new(),
+    };
+
+    // Variants always go through SWC, since they convert the module format
+    let (mut content, map) = match variant_index.is_some() || !mappings.is_empty() {
+      true => {
+        let persist_res = utils::swc::persist_resolutions(
+          locator,
+          &module.code,
+          source_map.as_deref(),
+          &mappings,
+          variant_index.map_or(module_format, |variant_index| opts.variants[variant_index].module_format),
+        );
+
+        match persist_res {
+          Ok(persisted) => (persisted.code.into_bytes(), persisted.map),
+          Err(mut err) => {
+            output.diagnostics.append(&mut err.diagnostics);
+            continue;
+          },
+        }
+      },
+
+      false => {
+        match module.bytes() {
+          Ok(content) => (content, source_map),
+          Err(mut err) => {
+            output.diagnostics.append(&mut err.diagnostics);
+            continue;
+          },
+        }
+      },
+    };
+
+    if opts.source_maps && module.mime_type == "text/javascript" {
+      if let Some(map) = map {
+        let map_path
+          = Path::from(&format!("{}.map", path.as_str()));
+        let map_basename
+          = map_path.as_str().rsplit('/').next().unwrap_or_default().to_string();
+
+        content.extend_from_slice(format!("\n//# sourceMappingURL={}\n", map_basename).as_bytes());
+
+        output.writes.push((map_path, map.into_bytes()));
+      }
+    }
+
+    output.files.push(OnEmitFile {
+      locator: locator.clone(),
+      module_format: variant_index.map(|variant_index| opts.variants[variant_index].module_format),
+      source_path: source_path.clone(),
+      path: path.clone(),
+      integrity: utils::get_integrity(&content),
+    });
+
+    // Declarations are written beside each JS output, with a matching
+    // flavor; their source path is what the package.json rewriter looks
+    // up for the types condition
+    if let Some(declaration) = &declaration {
+      let declaration_extension = match variant_index {
+        Some(_) => utils::get_declaration_extension(path.as_str()),
+        None => utils::get_declaration_extension(source_path.as_str()),
+      };
+
+      let declaration_path
+        = utils::replace_extension(&path, declaration_extension);
+      let declaration_content
+        = declaration.clone().into_bytes();
+
+      output.files.push(OnEmitFile {
+        locator: locator.clone(),
+        module_format: variant_index.map(|variant_index| opts.variants[variant_index].module_format),
+        source_path: utils::replace_extension(&source_path, utils::get_declaration_extension(source_path.as_str())),
+        path: declaration_path.clone(),
+        integrity: utils::get_integrity(&declaration_content),
+      });
+
+      output.writes.push((declaration_path, declaration_content));
+    }
+
+    output.writes.push((path, content));
+  }
+}
+
+// Files are written in parallel; we wait for all of them to settle before
+// returning, so that all write errors are reported at once
+async fn write_files(writes: Vec<(Path, Vec<u8>)>, diagnostics: &mut Vec<Diagnostic>) {
+  let tasks: Vec<_> = writes.into_iter().map(|(path, content)| {
+    tokio::spawn(write_file(path, content))
+  }).collect();
+
+  for task in tasks {
+    if let Err(diagnostic) = task.await.unwrap() {
+      diagnostics.push(diagnostic);
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct StreamedModules {
+  urls: HashSet<String>,
+  files: Vec<OnEmitFile>,
+  paths: Vec<Path>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl StreamedModules {
+  // The streamed files are written before we know whether the build will
+  // succeed; they're removed when it doesn't, so that it doesn't leave a
+  // partial dist folder behind
+  pub async fn discard(self) {
+    remove_files(&self.paths).await;
+  }
+}
+
+async fn remove_files(paths: &[Path]) {
+  let tasks: Vec<_> = paths.iter().map(|path| {
+    tokio::spawn(tokio::fs::remove_file(path.to_path_buf()))
+  }).collect();
+
+  // The files that can't be removed are left as-is; the build already
+  // reports the error that caused it to fail
+  for task in tasks {
+    let _ = task.await.unwrap();
+  }
+}
+
+// Modules whose output doesn't depend on the rest of the graph are written
+// as soon as the batch reports them, so that the writes overlap with the
+// compilation; emit then only has to take care of the remaining ones
+pub async fn emit_stream(opts: &OnEmitOpts, module_format: ModuleFormat, mut receiver: UnboundedReceiver<OnBatchModuleResult>) -> StreamedModules {
+  let mut streamed = StreamedModules::default();
+  let mut tasks = vec![];
+
+  while let Some(result) = receiver.recv().await {
+    // Errors are reported by emit, from the batch results
+    let module = match result.result {
+      Ok(module) => module,
+      Err(_) => continue,
+    };
+
+    // The imports can only be rewritten once we know where the
+    // dependencies are written
+    if module.resolutions.values().any(Option::is_some) {
+      continue;
+    }
+
+    streamed.urls.insert(result.locator.url.clone());
+
+    let dist_paths: Result<HashMap<_, _>, _> = get_variant_indices(opts, &module).into_iter().map(|variant_index| {
+      get_dist_path(opts, &result.locator, &module, variant_index)
+        .map(|path| ((result.locator.url.clone(), variant_index), path))
+    }).collect();
+
+    let dist_paths = match dist_paths {
+      Ok(dist_paths) => dist_paths,
+      Err(diagnostic) => {
+        streamed.diagnostics.push(diagnostic);
+        continue;
+      },
+    };
+
+    let mut output = EmitOutput::default();
+    render_module(opts, module_format, &result.locator, module, &dist_paths, &mut output);
+
+    streamed.files.append(&mut output.files);
+    streamed.diagnostics.append(&mut output.diagnostics);
+
+    tasks.extend(output.writes.into_iter().map(|(path, content)| {
+      streamed.paths.push(path.clone());
+      tokio::spawn(write_file(path, content))
+    }));
+  }
+
+  for task in tasks {
+    if let Err(diagnostic) = task.await.unwrap() {
+      streamed.diagnostics.push(diagnostic);
+    }
+  }
+
+  streamed
+}
+
+pub async fn emit(project: &Project, args: OnEmitArgs, streamed: StreamedModules) -> OnEmitResult {
+  let streamed_paths
+    = streamed.paths.clone();
+
+  let res
+    = emit_modules(project, args, streamed).await;
+
+  if res.result.is_err() {
+    remove_files(&streamed_paths).await;
+  }
+
+  res
+}
+
+async fn emit_modules(project: &Project, args: OnEmitArgs, streamed: StreamedModules) -> OnEmitResult {
+  let mut diagnostics
+    = streamed.diagnostics;
+
+  let mut modules = vec![];
+
+  for result in args.batch.results.into_values() {
+    match result.result {
+      Ok(module) => modules.push((result.locator, module)),
+      Err(mut err) => diagnostics.append(&mut err.diagnostics),
+    }
+  }
+
+  modules.sort_by(|a, b| {
+    a.0.url.cmp(&b.0.url)
+  });
+
+  // We need to know where all modules will be written before generating
+  // their code, since each variant references its own dependencies
+  let mut dist_paths = HashMap::new();
+
+  for (locator, module) in &modules {
+    for variant_index in get_variant_indices(&args.opts, module) {
+      match get_dist_path(&args.opts, locator, module, variant_index) {
+        Ok(path) => {
+          dist_paths.insert((locator.url.clone(), variant_index), path);
+        },
+
+        Err(diagnostic) => {
+          // The streamed modules already reported theirs
+          if !streamed.urls.contains(&locator.url) {
+            diagnostics.push(diagnostic);
+          }
+        },
+      }
+    }
+  }
+
+  if !diagnostics.is_empty() {
+    return OnEmitResult {
+      result: Err(CompilationError {diagnostics}),
+      dependencies: vec![],
+    };
+  }
+
+  let mut output = EmitOutput {
+    files: streamed.files,
+    ..Default::default()
+  };
+
+  for (locator, module) in modules {
+    if !streamed.urls.contains(&locator.url) {
+      render_module(&args.opts, args.module_format, &locator, module, &dist_paths, &mut output);
+    }
+  }
+
+  // Keeps the output independent from the order in which the modules were
+  // streamed; the files of a same module keep their relative order
+  output.files.sort_by(|a, b| {
+    a.locator.url.cmp(&b.locator.url)
+  });
+
+  let EmitOutput {mut files, mut writes, diagnostics: mut render_diagnostics} = output;
+  diagnostics.append(&mut render_diagnostics);
+
+  // Copied files are written as-is, unless they'd overwrite one of
+  // the files we generated
+  let generated_paths: HashSet<String> = streamed.paths.iter()
+    .chain(writes.iter().map(|(path, _)| path))
+    .map(|path| path.to_string())
+    .collect();
+
+  match find_copied_files(&args.opts).await {
+    Ok(copied_files) => for source_path in copied_files {
+      let path
+        = args.opts.dist_folder.join(&source_path.relative_to(&args.opts.source_folder));
+
+      if generated_paths.contains(&path.to_string()) {
+        continue;
+      }
+
+      let locator = match project.locator_from_path(&source_path, &[]) {
+        Some(locator) => locator,
+        None => {
+          diagnostics.push(Diagnostic::from_string(format!("Cannot copy {} (no locator matches this path)", source_path)));
+          continue;
+        },
+      };
+
+      let content = match tokio::fs::read(source_path.to_path_buf()).await {
+        Ok(content) => content,
+        Err(err) => {
+          diagnostics.push(Diagnostic::from_string(format!("Failed to read {} ({})", source_path, err)));
+          continue;
+        },
+      };
+
+      files.push(OnEmitFile {
+        locator,
+        module_format: None,
+        source_path,
+        path: path.clone(),
+        integrity: utils::get_integrity(&content),
+      });
+
+      writes.push((path, content));
+    },
+
+    Err(diagnostic) => {
+      diagnostics.push(diagnostic);
+    },
+  }
+
+  if !diagnostics.is_empty() {
+    return OnEmitResult {
+      result: Err(CompilationError {diagnostics}),
+      dependencies: vec![],
+    };
+  }
+
+  let mut manifest: BTreeMap<&str, Vec<ManifestEntry>> = BTreeMap::new();
+
+  for file in &files {
+    manifest.entry(file.locator.url.as_str()).or_default().push(ManifestEntry {
+      file: format!("./{}", file.path.relative_to(&args.opts.dist_folder)),
+      integrity: file.integrity.clone(),
+      format: file.module_format.map(|module_format| match module_format {
+        ModuleFormat::CommonJs => "cjs",
+        ModuleFormat::Esm => "esm",
+      }),
+    });
+  }
+
+  let manifest_content
+    = serde_json::to_string_pretty(&manifest).unwrap() + "\n";
+
+  writes.push((args.opts.dist_folder.join(&Path::from("manifest.json")), manifest_content.into_bytes()));
+
+  if args.opts.rewrite_package_json {
+    match rewrite_package_json(&args.opts, &files).await {
+      Ok(content) => writes.push((args.opts.dist_folder.join(&Path::from("package.json")), content)),
+      Err(mut err) => diagnostics.append(&mut err.diagnostics),
+    }
+  }
+
+  write_files(writes, &mut diagnostics).await;
+
+  OnEmitResult {
+    result: match diagnostics.is_empty() {
+      true => Ok(OnEmitResultData {files}),
+      false => Err(CompilationError {diagnostics}),
+    },
+    dependencies: vec![],
+  }
+}
@@ -1,13 +1,19 @@
 use base64::Engine;
 use pnp::fs::ZipCache;
+use std::sync::Arc;
 
 use crate::CompilationError;
+use crate::EsfusePlugin;
 use crate::Project;
 
 use crate::types::*;
 use crate::utils;
 
-pub async fn fetch(project: &Project, args: OnFetchArgs) -> OnFetchResult {
+fn call_fetch_hook(plugin: Arc<dyn EsfusePlugin>, call: HookCall<OnFetchArgs, OnFetchResult>) -> utils::BoxedFuture<Option<OnFetchResult>> {
+  plugin.fetch(call)
+}
+
+pub async fn fetch(project: &Arc<Project>, args: OnFetchArgs) -> OnFetchResult {
   if args.locator.kind == ModuleLocatorKind::External {
     return OnFetchResult {
       result: Err(CompilationError::from_string(format!("Cannot fetch this module ({} is external)", args.locator.url))),
@@ -15,82 +21,105 @@ pub async fn fetch(project: &Project, args: OnFetchArgs) -> OnFetchResult {
     };
   }
 
-  Project::resolve_plugin_hook(
-    &project.on_fetch,
-    &args.locator.url,
-    args.clone(),
-  ).await.unwrap_or_else(|| {
-    fetch_no_hooks(project, args)
+  let query_transform
+    = project.get_query_transform(&args.locator);
+
+  let fetch_res = match query_transform.map_or(true, |query_transform| query_transform.needs_source) {
+    true => Project::resolve_plugin_hook(
+      project.clone(),
+      PluginHookKind::Fetch,
+      call_fetch_hook,
+      args.locator.url.clone(),
+      args,
+      fetch_no_hooks,
+    ).await,
+
+    false => get_sourceless_fetch_result(args.locator),
+  };
+
+  let fetch_transform = query_transform.and_then(|query_transform| match query_transform.cb {
+    QueryTransformHook::Fetch(cb) => Some((query_transform, cb)),
+    QueryTransformHook::Transform(_) => None,
+  });
+
+  match (fetch_transform, fetch_res.result) {
+    (Some((query_transform, cb)), Ok(fetch_data)) if query_transform.applies_to(&fetch_data.mime_type) => {
+      let mut transform_res
+        = cb(query_transform.data.clone(), fetch_data).await;
+
+      transform_res.dependencies
+        .extend(fetch_res.dependencies);
+
+      transform_res
+    },
+
+    (_, result) => {
+      OnFetchResult {
+        result,
+        dependencies: fetch_res.dependencies,
+      }
+    },
+  }
+}
+
+fn get_sourceless_fetch_result(locator: ModuleLocator) -> OnFetchResult {
+  let ext
+    = utils::get_extension(&locator.specifier);
+
+  OnFetchResult {
+    result: Ok(OnFetchResultData {
+      locator,
+      mime_type: utils::get_mime_from_ext(&ext).to_string(),
+      source: String::new(),
+    }),
+    dependencies: vec![],
+  }
+}
+
+fn read_source(project: &Project, locator: &ModuleLocator) -> Result<Vec<u8>, CompilationError> {
+  let p = locator.physical_path(project).ok_or_else(|| {
+    CompilationError::from_string(format!("Cannot fetch this module (no fetcher configured for {})", locator.url))
+  })?;
+
+  let source_res = pnp::fs::vpath(p.to_path_buf().as_ref()).and_then(|res| match &res {
+    pnp::fs::VPath::Native(p)
+      => std::fs::read(p),
+    pnp::fs::VPath::Virtual(info @ pnp::fs::VPathInfo { zip_path: None, .. })
+      => std::fs::read(info.physical_base_path()),
+    pnp::fs::VPath::Virtual(info @ pnp::fs::VPathInfo { zip_path: Some(zip_path), .. })
+      => project.zip_cache.read(&info.physical_base_path(), zip_path),
+  });
+
+  source_res.map_err(|_| {
+    CompilationError::from_string(format!("Cannot fetch this module (an error happened while reading {})", locator.url))
   })
 }
 
 pub fn fetch_no_hooks(project: &Project, args: OnFetchArgs) -> OnFetchResult {
+  if project.get_query_transform(&args.locator).map_or(false, |query_transform| !query_transform.needs_source) {
+    return get_sourceless_fetch_result(args.locator);
+  }
+
   let ext = utils::get_extension(&args.locator.specifier);
 
-  let transform_opt
-    = args.locator.params.iter().rev().find(|p| p.name == "transform");
-
-  if let Some(transform) = transform_opt {
-    match transform.value.as_str() {
-      "url" => {
-        let stringified_url
-          = serde_json::to_string(&args.locator.without_query().url).unwrap();
-
-        return OnFetchResult {
-          result: Ok(OnFetchResultData {
-            locator: args.locator,
-            mime_type: String::from("text/javascript"),
-            source: format!("export default {};", stringified_url),
-          }),
-          dependencies: vec![],
-        };
-      },
-
-      _ => {},
-    }
-  }
+  let source_bytes = match read_source(project, &args.locator) {
+    Ok(source_bytes) => source_bytes,
+    Err(err) => return OnFetchResult {
+      result: Err(err),
+      dependencies: vec![],
+    },
+  };
 
-  match args.locator.physical_path(project) {
-    Some(p) => {
-      let source_res = pnp::fs::vpath(p.to_path_buf().as_ref()).and_then(|res| match &res {
-        pnp::fs::VPath::Native(p)
-          => std::fs::read(p),
-        pnp::fs::VPath::Virtual(info @ pnp::fs::VPathInfo { zip_path: None, .. })
-          => std::fs::read(info.physical_base_path()),
-        pnp::fs::VPath::Virtual(info @ pnp::fs::VPathInfo { zip_path: Some(zip_path), .. })
-          => project.zip_cache.read(&info.physical_base_path(), zip_path),
-      });
-
-      match source_res {
-        Ok(source_bytes) => {
-          let mime_type
-            = utils::get_mime_from_ext(&ext).to_string();
-
-          let source = match utils::is_binary_mime_type(&mime_type) {
-            true => base64::engine::general_purpose::STANDARD_NO_PAD.encode(&source_bytes),
-            false => String::from_utf8_lossy(&source_bytes).into_owned(),
-          };
-
-          OnFetchResult {
-            result: Ok(OnFetchResultData { locator: args.locator, mime_type, source }),
-            dependencies: vec![],
-          }
-        }
-
-        Err(_) => {
-          OnFetchResult {
-            result: Err(CompilationError::from_string(format!("Cannot fetch this module (an error happened while reading {})", args.locator.url))),
-            dependencies: vec![],
-          }    
-        }
-      }
-    }
+  let mime_type
+    = utils::get_mime_from_ext(&ext).to_string();
 
-    None => {
-      OnFetchResult {
-        result: Err(CompilationError::from_string(format!("Cannot fetch this module (no fetcher configured for {})", args.locator.url))),
-        dependencies: vec![].into(),
-      }
-    }
+  let source = match utils::is_binary_mime_type(&mime_type) {
+    true => base64::engine::general_purpose::STANDARD_NO_PAD.encode(&source_bytes),
+    false => String::from_utf8_lossy(&source_bytes).into_owned(),
+  };
+
+  OnFetchResult {
+    result: Ok(OnFetchResultData { locator: args.locator, mime_type, source }),
+    dependencies: vec![],
   }
 }
@@ -1,5 +1,7 @@
 pub mod batch;
+pub mod build;
 pub mod bundle;
+pub mod emit;
 pub mod fetch;
 pub mod resolve;
 pub mod transform;
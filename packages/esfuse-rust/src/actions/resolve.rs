@@ -1,11 +1,16 @@
 use arca::ToArcaPath;
 use parcel_resolver::{SpecifierType, ResolverError};
+use std::sync::Arc;
 
 use crate::types::*;
 use crate::utils;
-use crate::{CompilationError, Project};
+use crate::{CompilationError, EsfusePlugin, Project};
 
-pub async fn resolve(project: &Project, args: OnResolveArgs) -> OnResolveResult {
+fn call_resolve_hook(plugin: Arc<dyn EsfusePlugin>, call: HookCall<OnResolveArgs, OnResolveResult>) -> utils::BoxedFuture<Option<OnResolveResult>> {
+  plugin.resolve(call)
+}
+
+pub async fn resolve(project: &Arc<Project>, args: OnResolveArgs) -> OnResolveResult {
   if let Some(locator) = project.locator(&args.request) {
     return OnResolveResult {
       result: Ok(OnResolveResultData { locator }),
@@ -14,12 +19,13 @@ pub async fn resolve(project: &Project, args: OnResolveArgs) -> OnResolveResult
   }
 
   Project::resolve_plugin_hook(
-    &project.on_resolve,
-    &args.request.clone(),
-    args.clone(),
-  ).await.unwrap_or_else(|| {
-    resolve_no_hooks(project, args)
-  })
+    project.clone(),
+    PluginHookKind::Resolve,
+    call_resolve_hook,
+    args.request.clone(),
+    args,
+    resolve_no_hooks,
+  ).await
 }
 
 pub fn resolve_no_hooks(project: &Project, args: OnResolveArgs) -> OnResolveResult {
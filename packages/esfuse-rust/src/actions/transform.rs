@@ -1,5 +1,8 @@
+use std::sync::Arc;
+
+use crate::classes::plugin::{applies_to, run_hook};
 use crate::types::*;
-use crate::Project;
+use crate::{CompilationError, Project};
 
 use super::fetch::{fetch_no_hooks, fetch};
 
@@ -26,7 +29,75 @@ pub fn transform_no_hooks(project: &Project, args: OnTransformArgs) -> OnTransfo
   }
 }
 
-pub async fn transform(project: &Project, args: OnTransformArgs) -> OnTransformResult {
+// Unlike the resolve and fetch hooks, all matching transform hooks are
+// applied, each of them receiving the output of the previous one
+async fn run_transform_hooks(project: &Project, stage: TransformStage, args: &OnTransformArgs, data: OnTransformResultData) -> OnTransformResult {
+  let mut data = data;
+  let mut dependencies = vec![];
+
+  for plugin in &project.plugins {
+    if !applies_to(plugin.as_ref(), PluginHookKind::Transform, &args.locator.url) {
+      continue;
+    }
+
+    let hook_res = run_hook(project, plugin.as_ref(), &args.locator.url, plugin.clone().transform(OnTransformHookArgs {
+      locator: args.locator.clone(),
+      stage,
+      data: data.clone(),
+      opts: args.opts.clone(),
+    })).await;
+
+    if let Some(mut hook_res) = hook_res {
+      dependencies.append(&mut hook_res.dependencies);
+
+      match hook_res.result {
+        Ok(hook_data) => {
+          data = hook_data;
+        },
+
+        Err(err) => {
+          return OnTransformResult {
+            result: Err(err),
+            dependencies,
+          };
+        },
+      }
+    }
+  }
+
+  OnTransformResult {
+    result: Ok(data),
+    dependencies,
+  }
+}
+
+// The map generated by the transform describes how the code returned by
+// the before hooks got compiled; extending it with the hook map lets us
+// trace the compiled code back to the original source
+fn compose_before_map(map: Option<String>, before_map: &str) -> Result<Option<String>, CompilationError> {
+  let map = match map {
+    Some(map) => map,
+    None => return Ok(None),
+  };
+
+  let mut source_map = parcel_sourcemap::SourceMap::from_json("/", &map).map_err(|err| {
+    CompilationError::from_string(format!("Invalid source map returned by the transform ({})", err))
+  })?;
+
+  let mut before_source_map = parcel_sourcemap::SourceMap::from_json("/", before_map).map_err(|err| {
+    CompilationError::from_string(format!("Invalid source map returned by a transform hook ({})", err))
+  })?;
+
+  source_map.extends(&mut before_source_map).map_err(|err| {
+    CompilationError::from_string(format!("Failed to compose the source map returned by a transform hook ({})", err))
+  })?;
+
+  source_map.to_json(None).map(Some).map_err(|err| {
+    CompilationError::from_string(format!("Failed to serialize the source map ({})", err))
+  })
+}
+
+pub async fn transform(project: &Arc<Project>, args: OnTransformArgs) -> OnTransformResult {
   let fetch_res
     = fetch(project, OnFetchArgs {
         locator: args.locator.clone(),
@@ -35,16 +106,96 @@ pub async fn transform(project: &Project, args: OnTransformArgs) -> OnTransformR
         },
       }).await;
 
-  match fetch_res.result {
-    Ok(fetch_data) => {
-      crate::transforms::transform(project, fetch_data, args)
+  let fetch_data = match fetch_res.result {
+    Ok(fetch_data) => fetch_data,
+    Err(err) => return OnTransformResult {
+      result: Err(err),
+      dependencies: fetch_res.dependencies,
     },
+  };
 
-    Err(err) => {
-      OnTransformResult {
-        result: Err(err),
-        dependencies: fetch_res.dependencies,
-      }
+  let mut dependencies
+    = fetch_res.dependencies;
+
+  let before_res = run_transform_hooks(project, TransformStage::Before, &args, OnTransformResultData {
+    mime_type: fetch_data.mime_type,
+    code: fetch_data.source,
+    map: None,
+    declaration: None,
+    declaration_error: None,
+    imports: vec![],
+  }).await;
+
+  dependencies.extend(before_res.dependencies);
+
+  let before_data = match before_res.result {
+    Ok(before_data) => before_data,
+    Err(err) => return OnTransformResult {
+      result: Err(err),
+      dependencies,
     },
+  };
+
+  let before_map
+    = before_data.map;
+  let before_imports
+    = before_data.imports;
+
+  let transform_project
+    = project.clone();
+  let transform_args
+    = args.clone();
+
+  // Parsing and codegen are CPU-bound; running them on the blocking pool
+  // keeps the async workers available for the hooks' I/O
+  let transform_task = tokio::task::spawn_blocking(move || {
+    crate::transforms::transform(&transform_project, OnFetchResultData {
+      locator: fetch_data.locator,
+      mime_type: before_data.mime_type,
+      source: before_data.code,
+    }, transform_args)
+  });
+
+  let transform_res = match transform_task.await {
+    Ok(transform_res) => transform_res,
+    Err(err) => std::panic::resume_unwind(err.into_panic()),
+  };
+
+  dependencies.extend(transform_res.dependencies);
+
+  let mut transform_data = match transform_res.result {
+    Ok(transform_data) => transform_data,
+    Err(err) => return OnTransformResult {
+      result: Err(err),
+      dependencies,
+    },
+  };
+
+  if let Some(before_map) = &before_map {
+    match compose_before_map(transform_data.map.take(), before_map) {
+      Ok(map) => transform_data.map = map,
+      Err(err) => return OnTransformResult {
+        result: Err(err),
+        dependencies,
+      },
+    }
+  }
+
+  // The imports declared by the before hooks may not appear in the code
+  // they returned (for instance if they're only needed at runtime)
+  for import in before_imports {
+    if !transform_data.imports.iter().any(|existing| existing.specifier == import.specifier) {
+      transform_data.imports.push(import);
+    }
+  }
+
+  let after_res
+    = run_transform_hooks(project, TransformStage::After, &args, transform_data).await;
+
+  dependencies.extend(after_res.dependencies);
+
+  OnTransformResult {
+    result: after_res.result,
+    dependencies,
   }
 }
@@ -1,3 +1,5 @@
+pub(crate) mod plugin;
 mod project;
 
+pub use plugin::EsfusePlugin;
 pub use project::Project;
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::types::*;
+use crate::utils;
+use crate::utils::errors::Highlight;
+use crate::{CompilationError, Project};
+
+pub trait EsfusePlugin: Send + Sync {
+  // Also the key of the hook stats, which are aggregated per plugin
+  fn name(&self) -> &str;
+
+  // The hooks that aren't listed aren't called at all, and don't count
+  // towards the plugin stats
+  fn hooks(&self) -> &[PluginHookKind];
+
+  // Plugins without filter are called for every module
+  fn filter(&self) -> Option<&PluginFilter> {
+    None
+  }
+
+  // Plugins without timeout use the project one
+  fn timeout(&self) -> Option<Duration> {
+    None
+  }
+
+  fn resolve(self: Arc<Self>, _call: HookCall<OnResolveArgs, OnResolveResult>) -> utils::BoxedFuture<Option<OnResolveResult>> {
+    Box::pin(async { None })
+  }
+
+  fn fetch(self: Arc<Self>, _call: HookCall<OnFetchArgs, OnFetchResult>) -> utils::BoxedFuture<Option<OnFetchResult>> {
+    Box::pin(async { None })
+  }
+
+  fn transform(self: Arc<Self>, _args: OnTransformHookArgs) -> utils::BoxedFuture<Option<OnTransformResult>> {
+    Box::pin(async { None })
+  }
+
+  fn render_chunk(self: Arc<Self>, _args: OnRenderChunkArgs) -> utils::BoxedFuture<Option<OnRenderChunkResult>> {
+    Box::pin(async { None })
+  }
+
+  fn generate_bundle(self: Arc<Self>, _args: OnGenerateBundleArgs) -> utils::BoxedFuture<Option<OnBundleResult>> {
+    Box::pin(async { None })
+  }
+}
+
+pub(crate) fn applies_to(plugin: &dyn EsfusePlugin, kind: PluginHookKind, subject: &str) -> bool {
+  plugin.hooks().contains(&kind) && plugin.filter().map_or(true, |filter| filter.matches(subject))
+}
+
+pub(crate) async fn run_hook<TRes: HookResult>(project: &Project, plugin: &dyn EsfusePlugin, subject: &str, future: utils::BoxedFuture<Option<TRes>>) -> Option<TRes> {
+  run_hook_excluding(project, plugin, subject, future, &AtomicU64::new(0)).await
+}
+
+// The nanoseconds accumulated in nested_ns while the hook runs (typically
+// by its continuation) are excluded from its stats
+pub(crate) async fn run_hook_excluding<TRes: HookResult>(project: &Project, plugin: &dyn EsfusePlugin, subject: &str, future: utils::BoxedFuture<Option<TRes>>, nested_ns: &AtomicU64) -> Option<TRes> {
+  let start = Instant::now();
+
+  let timeout = plugin.timeout()
+    .or(project.hook_timeout);
+
+  let (res, timed_out) = match timeout {
+    Some(timeout) => match tokio::time::timeout(timeout, future).await {
+      Ok(res) => (res, false),
+      Err(_) => (Some(TRes::from_error(CompilationError::from_string_with_highlight(
+        format!("{} timed out after {}ms while processing {}", plugin.name(), timeout.as_millis(), subject),
+        Highlight {
+          source: Some(subject.to_string()),
+          subject: Some(plugin.name().to_string()),
+          label: None,
+          span: None,
+        },
+      ))), true),
+    },
+
+    None => (future.await, false),
+  };
+
+  let self_time = start.elapsed()
+    .saturating_sub(Duration::from_nanos(nested_ns.load(Ordering::Relaxed)));
+
+  project.record_hook_call(plugin.name(), self_time, timed_out);
+
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+
+  struct RecordingPlugin {
+    calls: Mutex<Vec<String>>,
+    filter: PluginFilter,
+  }
+
+  impl EsfusePlugin for RecordingPlugin {
+    fn name(&self) -> &str {
+      "recording"
+    }
+
+    fn hooks(&self) -> &[PluginHookKind] {
+      &[PluginHookKind::Resolve, PluginHookKind::Fetch, PluginHookKind::Transform]
+    }
+
+    fn filter(&self) -> Option<&PluginFilter> {
+      Some(&self.filter)
+    }
+
+    fn resolve(self: Arc<Self>, call: HookCall<OnResolveArgs, OnResolveResult>) -> utils::BoxedFuture<Option<OnResolveResult>> {
+      Box::pin(async move {
+        self.calls.lock().unwrap().push(format!("resolve {}", call.args.request));
+        Some((call.next)(call.args).await)
+      })
+    }
+
+    fn fetch(self: Arc<Self>, call: HookCall<OnFetchArgs, OnFetchResult>) -> utils::BoxedFuture<Option<OnFetchResult>> {
+      Box::pin(async move {
+        self.calls.lock().unwrap().push(format!("fetch {}", call.args.locator.url));
+        Some((call.next)(call.args).await)
+      })
+    }
+
+    fn transform(self: Arc<Self>, args: OnTransformHookArgs) -> utils::BoxedFuture<Option<OnTransformResult>> {
+      Box::pin(async move {
+        self.calls.lock().unwrap().push(format!("transform {:?} {}", args.stage, args.locator.url));
+        None
+      })
+    }
+  }
+
+  #[tokio::test]
+  async fn it_calls_the_plugin_hooks_in_order() {
+    let root = arca::Path::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../../fixtures"));
+
+    let plugin = Arc::new(RecordingPlugin {
+      calls: Default::default(),
+      filter: PluginFilter {
+        regexp: fancy_regex::Regex::new(r"log\.ts$").unwrap(),
+        params: vec![],
+      },
+    });
+
+    let mut project = Project::new(&root);
+    project.register_plugin(plugin.clone());
+
+    let project = Arc::new(project);
+
+    let resolve_res = crate::actions::resolve::resolve(&project, OnResolveArgs {
+      kind: ResolutionKind::ImportDeclaration,
+      request: String::from("./log.ts"),
+      issuer: project.locator_from_path(&root.join(&arca::Path::from("import-raw.ts")), &[]),
+      span: None,
+      opts: Default::default(),
+    }).await;
+
+    let locator = resolve_res.result
+      .expect("The resolution should have succeeded")
+      .locator;
+
+    let transform_res = crate::actions::transform::transform(&project, OnTransformArgs {
+      locator,
+      opts: Default::default(),
+    }).await;
+
+    assert!(transform_res.result.is_ok());
+
+    assert_eq!(*plugin.calls.lock().unwrap(), vec![
+      "resolve ./log.ts",
+      "fetch /_dev/file/app/log.ts",
+      "transform Before /_dev/file/app/log.ts",
+      "transform After /_dev/file/app/log.ts",
+    ]);
+
+    let stats = project.hook_stats();
+
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].name, "recording");
+    assert_eq!(stats[0].calls, 4);
+  }
+}
@@ -4,15 +4,27 @@ use lazy_static::lazy_static;
 use parcel_resolver::CacheCow;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::types::*;
 use crate::utils;
 
+use super::EsfusePlugin;
+
 pub struct Project {
   pub root: Cow<'static, Path>,
 
-  pub on_resolve: Vec<PluginHook<OnResolveArgs, OnResolveResult>>,
-  pub on_fetch: Vec<PluginHook<OnFetchArgs, OnFetchResult>>,
+  // The hooks of each kind are called in the order their plugins got
+  // registered in
+  pub plugins: Vec<Arc<dyn EsfusePlugin>>,
+  pub hook_timeout: Option<Duration>,
+
+  pub query_transforms: HashMap<String, QueryTransform>,
+
+  // Shared with the projects this one replaces (see inherit_hook_stats)
+  pub(crate) hook_stats: Arc<Mutex<HashMap<String, HookStats>>>,
 
   pub(crate) resolver: parcel_resolver::Resolver<'static, parcel_resolver::OsFileSystem>,
   pub(crate) zip_cache: pnp::fs::LruZipCache<Vec<u8>>,
@@ -23,17 +35,56 @@ pub struct Project {
   pub(crate) package_json_finder: utils::FileFinder,
 }
 
+pub type ChainedHook<TArgs, TRes> = fn (plugin: Arc<dyn EsfusePlugin>, call: HookCall<TArgs, TRes>) -> utils::BoxedFuture<Option<TRes>>;
+
 impl Project {
-  pub async fn resolve_plugin_hook<'a, TArgs : Clone, TRes>(hooks: &'a Vec<PluginHook<TArgs, TRes>>, str: &str, args: TArgs) -> Option<TRes> {
-    for hook in hooks {
-      if hook.regexp.is_match(str).unwrap() {
-        if let Some(res) = (hook.cb)(hook.data.clone(), args.clone()).await {
-          return Some(res)
+  pub fn resolve_plugin_hook<TArgs, TRes>(project: Arc<Project>, kind: PluginHookKind, hook: ChainedHook<TArgs, TRes>, str: String, args: TArgs, fallback: fn (&Project, TArgs) -> TRes) -> utils::BoxedFuture<TRes>
+  where TArgs: Clone + Send + 'static, TRes: HookResult + Send + 'static {
+    Self::resolve_plugin_hook_from(project, kind, hook, str, 0, args, fallback)
+  }
+
+  fn resolve_plugin_hook_from<TArgs, TRes>(project: Arc<Project>, kind: PluginHookKind, hook: ChainedHook<TArgs, TRes>, str: String, index: usize, args: TArgs, fallback: fn (&Project, TArgs) -> TRes) -> utils::BoxedFuture<TRes>
+  where TArgs: Clone + Send + 'static, TRes: HookResult + Send + 'static {
+    Box::pin(async move {
+      for (plugin_index, plugin) in project.plugins.iter().enumerate().skip(index) {
+        if !super::plugin::applies_to(plugin.as_ref(), kind, &str) {
+          continue;
+        }
+
+        // Calling the continuation runs the following hooks, and ultimately
+        // the default implementation, so plugins can wrap their results
+        let next_project = project.clone();
+        let next_str = str.clone();
+
+        // The time spent in the continuation is tracked so that it doesn't
+        // count towards this hook's stats
+        let nested_ns = Arc::new(AtomicU64::new(0));
+        let next_nested_ns = nested_ns.clone();
+
+        let next: HookNext<TArgs, TRes> = Arc::new(move |next_args| -> utils::BoxedFuture<TRes> {
+          let nested_ns = next_nested_ns.clone();
+          let next_res = Self::resolve_plugin_hook_from(next_project.clone(), kind, hook, next_str.clone(), plugin_index + 1, next_args, fallback);
+
+          Box::pin(async move {
+            let start = Instant::now();
+            let res = next_res.await;
+
+            nested_ns.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+            res
+          })
+        });
+
+        let future
+          = hook(plugin.clone(), HookCall {args: args.clone(), next});
+
+        if let Some(res) = super::plugin::run_hook_excluding(&project, plugin.as_ref(), &str, future, &nested_ns).await {
+          return res;
         }
       }
-    }
 
-    None
+      fallback(&project, args)
+    })
   }
 
   pub fn new(root: &Path) -> Self {
@@ -43,8 +94,12 @@ impl Project {
     let mut project = Self {
       root: Cow::Owned(root.clone()),
 
-      on_resolve: Default::default(),
-      on_fetch: Default::default(),
+      plugins: Default::default(),
+      hook_timeout: None,
+
+      query_transforms: Default::default(),
+
+      hook_stats: Default::default(),
 
       resolver: parcel_resolver::Resolver::parcel(
         Cow::Owned(root.to_path_buf()),
@@ -61,6 +116,24 @@ impl Project {
   
     project.register_ns("app", &root.clone());
 
+    let builtin_query_transforms: [(&str, OnQueryCompileHook, Option<&str>, bool, bool); 5] = [
+      ("css", crate::transforms::query::transform_css, Some("text/css"), true, false),
+      ("inline", crate::transforms::query::transform_inline, None, true, true),
+      ("meta", crate::transforms::query::transform_meta, Some("text/markdown"), true, true),
+      ("raw", crate::transforms::query::transform_raw, None, true, true),
+      ("url", crate::transforms::query::transform_url, None, false, false),
+    ];
+
+    for (name, cb, mime_type, needs_source, bare_flag) in builtin_query_transforms {
+      project.register_query_transform(name, QueryTransform {
+        cb: QueryTransformHook::Transform(cb),
+        data: Arc::new(Box::new(())),
+        mime_type: mime_type.map(str::to_string),
+        needs_source,
+        bare_flag,
+      });
+    }
+
     project
   }
 
@@ -76,6 +149,71 @@ impl Project {
     );
   }
 
+  pub fn register_query_transform<S: AsRef<str>>(&mut self, name: S, transform: QueryTransform) {
+    self.query_transforms.insert(
+      name.as_ref().to_string(),
+      transform,
+    );
+  }
+
+  // Query transforms are selected through the transform parameter
+  // (`?transform=url`), or through a flag named after them (`?raw`) for
+  // the ones that opted into it
+  pub fn get_query_transform(&self, locator: &ModuleLocator) -> Option<&QueryTransform> {
+    let transform_param = locator.params.iter().rev()
+      .find(|p| p.name == "transform");
+
+    match transform_param {
+      Some(transform_param) => self.query_transforms.get(&transform_param.value),
+      None => locator.params.iter()
+        .filter(|p| p.value.is_empty())
+        .filter_map(|p| self.query_transforms.get(&p.name))
+        .find(|transform| transform.bare_flag),
+    }
+  }
+
+  pub fn register_plugin(&mut self, plugin: Arc<dyn EsfusePlugin>) {
+    self.plugins.push(plugin);
+  }
+
+  // Lets the stats survive reconfigurations; the operations still running
+  // against the previous project keep recording into the same stats
+  pub fn inherit_hook_stats(&mut self, previous: &Project) {
+    self.hook_stats = previous.hook_stats.clone();
+  }
+
+  pub(crate) fn record_hook_call(&self, name: &str, duration: Duration, timed_out: bool) {
+    let mut hook_stats
+      = self.hook_stats.lock().unwrap();
+
+    let stats = hook_stats.entry(name.to_string()).or_insert_with(|| HookStats {
+      name: name.to_string(),
+      ..Default::default()
+    });
+
+    let ms
+      = duration.as_secs_f64() * 1000.0;
+
+    stats.calls += 1;
+    stats.total_ms += ms;
+    stats.max_ms = stats.max_ms.max(ms);
+
+    if timed_out {
+      stats.timeouts += 1;
+    }
+  }
+
+  pub fn hook_stats(&self) -> Vec<HookStats> {
+    let mut hook_stats: Vec<HookStats>
+      = self.hook_stats.lock().unwrap().values().cloned().collect();
+
+    hook_stats.sort_by(|a, b| {
+      b.total_ms.total_cmp(&a.total_ms)
+    });
+
+    hook_stats
+  }
+
   pub fn root_ns<P: AsRef<str>>(&self, ns: P) -> &Path {
     self.ns_to_path.get(ns.as_ref()).unwrap()
   }
@@ -5,7 +5,7 @@ pub mod actions;
 pub mod types;
 pub mod utils;
 
-pub use classes::Project;
+pub use classes::{EsfusePlugin, Project};
 pub use utils::errors::CompilationError;
 
 #[macro_use]
@@ -40,7 +40,7 @@ async fn main() {
   let bundle = esfuse::actions::bundle::bundle(project_arc, esfuse::types::OnBundleArgs {
     locator: entry_point,
     opts: Default::default(),
-  }).await.result.unwrap();
+  }, &Default::default()).await.result.unwrap();
 
   print!("{}", bundle.code);
 }
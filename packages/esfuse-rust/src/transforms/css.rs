@@ -41,6 +41,9 @@ pub fn transform_css(_project: &Project, fetch_data: OnFetchResultData, _args: O
           code: codegen.code,
           map: None,
 
+          declaration: None,
+          declaration_error: None,
+
           imports: vec![],
         }),
         dependencies: vec![],
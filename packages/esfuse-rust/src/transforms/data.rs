@@ -0,0 +1,264 @@
+use crate::CompilationError;
+use crate::types::*;
+use crate::utils::errors::Highlight;
+use crate::Project;
+
+use super::swc::transform_swc;
+
+const RESERVED_WORDS: &[&str] = &[
+  "arguments", "await", "break", "case", "catch", "class", "const", "continue",
+  "debugger", "default", "delete", "do", "else", "enum", "eval", "export",
+  "extends", "false", "finally", "for", "function", "if", "implements", "import",
+  "in", "instanceof", "interface", "let", "new", "null", "package", "private",
+  "protected", "public", "return", "static", "super", "switch", "this", "throw",
+  "true", "try", "typeof", "var", "void", "while", "with", "yield",
+];
+
+const DATA_IDENTIFIER: &str = "$esfuse$data";
+
+fn is_valid_export_name(name: &str) -> bool {
+  let mut chars = name.chars();
+
+  let is_valid_start = chars.next().map_or(false, |c| {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+  });
+
+  is_valid_start
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    && !RESERVED_WORDS.contains(&name)
+    && name != DATA_IDENTIFIER
+}
+
+fn get_child_path(path: &str, key: &str) -> String {
+  match path.is_empty() {
+    true => key.to_string(),
+    false => format!("{}.{}", path, key),
+  }
+}
+
+// JSON can't represent NaN and infinite numbers; rather than silently
+// turning them into null, we report the offending key path
+fn get_non_finite_error(path: &str, url: &str) -> CompilationError {
+  let location = match path.is_empty() {
+    true => String::from("at the root of the document"),
+    false => format!("at {}", path),
+  };
+
+  CompilationError::from_string_with_highlight(format!("Non-finite numbers can't be represented in data modules ({})", location), Highlight {
+    source: Some(url.to_string()),
+    subject: None,
+    label: None,
+    span: None,
+  })
+}
+
+fn find_yaml_non_finite(value: &serde_yaml::Value, path: &str) -> Option<String> {
+  match value {
+    serde_yaml::Value::Number(value) => {
+      value.as_f64().filter(|value| !value.is_finite()).map(|_| path.to_string())
+    },
+
+    serde_yaml::Value::Sequence(values) => {
+      values.iter().enumerate().find_map(|(index, value)| find_yaml_non_finite(value, &format!("{}[{}]", path, index)))
+    },
+
+    serde_yaml::Value::Mapping(fields) => {
+      fields.iter().find_map(|(key, value)| {
+        let key = match key {
+          serde_yaml::Value::String(key) => key.clone(),
+          _ => serde_yaml::to_string(key).unwrap_or_default().trim_end().to_string(),
+        };
+
+        find_yaml_non_finite(value, &get_child_path(path, &key))
+      })
+    },
+
+    serde_yaml::Value::Tagged(tagged) => {
+      find_yaml_non_finite(&tagged.value, path)
+    },
+
+    _ => None,
+  }
+}
+
+fn toml_to_json(value: toml::Value, path: &str) -> Result<serde_json::Value, String> {
+  Ok(match value {
+    toml::Value::String(value) => serde_json::Value::String(value),
+    toml::Value::Integer(value) => serde_json::Value::from(value),
+    toml::Value::Boolean(value) => serde_json::Value::Bool(value),
+
+    toml::Value::Float(value) => {
+      serde_json::Number::from_f64(value).map(serde_json::Value::Number).ok_or_else(|| path.to_string())?
+    },
+
+    // JavaScript has no equivalent to the TOML local dates and times,
+    // so we expose them as strings
+    toml::Value::Datetime(value) => serde_json::Value::String(value.to_string()),
+
+    toml::Value::Array(values) => {
+      serde_json::Value::Array(values.into_iter().enumerate().map(|(index, value)| {
+        toml_to_json(value, &format!("{}[{}]", path, index))
+      }).collect::<Result<_, _>>()?)
+    },
+
+    toml::Value::Table(fields) => {
+      serde_json::Value::Object(fields.into_iter().map(|(key, value)| {
+        let child_path = get_child_path(path, &key);
+        toml_to_json(value, &child_path).map(|value| (key, value))
+      }).collect::<Result<_, _>>()?)
+    },
+  })
+}
+
+// Serializes the value as a JavaScript expression; it's the same as the
+// JSON representation, except that `__proto__` keys must be computed to
+// define an own property rather than set the object prototype
+fn to_js_literal(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::Array(values) => {
+      format!("[{}]", values.iter().map(to_js_literal).collect::<Vec<_>>().join(","))
+    },
+
+    serde_json::Value::Object(fields) => {
+      let fields: Vec<String> = fields.iter().map(|(key, value)| {
+        let key_literal = serde_json::Value::String(key.clone()).to_string();
+
+        match key == "__proto__" {
+          true => format!("[{}]:{}", key_literal, to_js_literal(value)),
+          false => format!("{}:{}", key_literal, to_js_literal(value)),
+        }
+      }).collect();
+
+      format!("{{{}}}", fields.join(","))
+    },
+
+    _ => value.to_string(),
+  }
+}
+
+fn generate_data_module(value: &serde_json::Value, module_format: ModuleFormat) -> String {
+  // CommonJS consumers expect to get the data itself when requiring the
+  // file, as they would with Node, not a namespace object
+  if module_format == ModuleFormat::CommonJs {
+    return format!("module.exports = {};\n", to_js_literal(value));
+  }
+
+  let mut generated = format!(
+    concat!(
+      "const {} = {};\n",
+      "\n",
+      "export default {};\n",
+    ),
+
+    DATA_IDENTIFIER,
+    to_js_literal(value),
+    DATA_IDENTIFIER,
+  );
+
+  // Top-level keys are also exposed as named exports, as long as they
+  // are valid identifiers
+  if let serde_json::Value::Object(fields) = value {
+    let export_names: Vec<&String> = fields.keys()
+      .filter(|key| is_valid_export_name(key))
+      .collect();
+
+    if !export_names.is_empty() {
+      generated.push('\n');
+    }
+
+    for name in export_names {
+      generated.push_str(&format!("export const {} = {}.{};\n", name, DATA_IDENTIFIER, name));
+    }
+  }
+
+  generated
+}
+
+pub fn transform_json(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  let value: serde_json::Value = match serde_json::from_str(&fetch_data.source) {
+    Ok(value) => value,
+    Err(err) => return OnTransformResult {
+      result: Err(CompilationError::from_json(&err, fetch_data.locator.url.clone())),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    },
+  };
+
+  let module_format
+    = args.opts.swc.module_format;
+
+  transform_swc(project, OnFetchResultData {
+    source: generate_data_module(&value, module_format),
+    ..fetch_data
+  }, args)
+}
+
+pub fn transform_yaml(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  let yaml_value: serde_yaml::Value = match serde_yaml::from_str(&fetch_data.source) {
+    Ok(yaml_value) => yaml_value,
+    Err(err) => return OnTransformResult {
+      result: Err(CompilationError::from_yaml(&err, fetch_data.locator.url.clone())),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    },
+  };
+
+  if let Some(path) = find_yaml_non_finite(&yaml_value, "") {
+    return OnTransformResult {
+      result: Err(get_non_finite_error(&path, &fetch_data.locator.url)),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    };
+  }
+
+  let value: serde_json::Value = match serde_yaml::from_value(yaml_value) {
+    Ok(value) => value,
+    Err(err) => return OnTransformResult {
+      result: Err(CompilationError::from_yaml(&err, fetch_data.locator.url.clone())),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    },
+  };
+
+  let module_format
+    = args.opts.swc.module_format;
+
+  transform_swc(project, OnFetchResultData {
+    source: generate_data_module(&value, module_format),
+    ..fetch_data
+  }, args)
+}
+
+pub fn transform_toml(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  let value: toml::Value = match toml::from_str(&fetch_data.source) {
+    Ok(value) => value,
+    Err(err) => return OnTransformResult {
+      result: Err(CompilationError::from_toml(&err, &fetch_data.source, fetch_data.locator.url.clone())),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    },
+  };
+
+  let value = match toml_to_json(value, "") {
+    Ok(value) => value,
+    Err(path) => return OnTransformResult {
+      result: Err(get_non_finite_error(&path, &fetch_data.locator.url)),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    },
+  };
+
+  let module_format
+    = args.opts.swc.module_format;
+
+  transform_swc(project, OnFetchResultData {
+    source: generate_data_module(&value, module_format),
+    ..fetch_data
+  }, args)
+}
@@ -2,31 +2,46 @@ use crate::types::*;
 use crate::Project;
 
 mod css;
+mod data;
 mod mdx;
 mod swc;
 
-pub use self::swc::OnTransformSwcOpts;
+pub mod query;
+
+pub use self::swc::{OnTransformSwcOpts, SwcBuiltins, SwcPlugin};
 
 pub fn transform(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  if let Some(query_transform) = project.get_query_transform(&fetch_data.locator) {
+    if let QueryTransformHook::Transform(cb) = query_transform.cb {
+      if query_transform.applies_to(&fetch_data.mime_type) {
+        return cb(query_transform.data.clone(), project, fetch_data, args);
+      }
+    }
+  }
+
   match fetch_data.mime_type.as_str() {
     "text/css" => {
-      if fetch_data.locator.params.iter().any(|pair| pair.name == "transform" && pair.value == "css") {
-        self::css::transform_css(project, fetch_data, args)
-      } else {
-        self::css::transform_css_js(project, fetch_data, args)
-      }
+      self::css::transform_css_js(project, fetch_data, args)
+    }
+
+    "application/json" => {
+      self::data::transform_json(project, fetch_data, args)
     }
 
     "text/javascript" => {
       self::swc::transform_swc(project, fetch_data, args)
     }
 
+    "text/yaml" => {
+      self::data::transform_yaml(project, fetch_data, args)
+    }
+
+    "text/toml" => {
+      self::data::transform_toml(project, fetch_data, args)
+    }
+
     "text/markdown" => {
-      if fetch_data.locator.params.iter().any(|pair| pair.name == "meta") {
-        self::mdx::transform_mdx_meta(project, fetch_data, args)
-      } else {
-        self::mdx::transform_mdx(project, fetch_data, args)
-      }
+      self::mdx::transform_mdx(project, fetch_data, args)
     }
 
     _ => {
@@ -35,6 +50,8 @@ pub fn transform(project: &Project, fetch_data: OnFetchResultData, args: OnTrans
           mime_type: fetch_data.mime_type,
           code: fetch_data.source,
           map: None,
+          declaration: None,
+          declaration_error: None,
           imports: vec![],
         }),
         dependencies: vec![],
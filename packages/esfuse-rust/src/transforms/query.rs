@@ -0,0 +1,70 @@
+use base64::Engine;
+use std::sync::Arc;
+
+use crate::types::*;
+use crate::utils;
+use crate::{CompilationError, Project};
+
+fn transform_generated_module(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs, value: &str) -> OnTransformResult {
+  super::swc::transform_swc(project, OnFetchResultData {
+    mime_type: String::from("text/javascript"),
+    source: format!("export default {};", serde_json::to_string(value).unwrap()),
+    ..fetch_data
+  }, args)
+}
+
+// Binary files are fetched as base64, so we must decode them to get
+// their actual content
+fn get_source_bytes(fetch_data: &OnFetchResultData) -> Result<Vec<u8>, CompilationError> {
+  match utils::is_binary_mime_type(&fetch_data.mime_type) {
+    true => utils::decode_binary_source(&fetch_data.source),
+    false => Ok(fetch_data.source.clone().into_bytes()),
+  }
+}
+
+fn get_inline_mime_type(ext: &str) -> String {
+  match utils::get_mime_from_ext(ext) {
+    "text/plain" => mime_guess::from_ext(ext.trim_start_matches('.')).first_or_octet_stream().to_string(),
+    mime_type => mime_type.to_string(),
+  }
+}
+
+pub fn transform_css(_data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  super::css::transform_css(project, fetch_data, args)
+}
+
+fn get_data_url(fetch_data: &OnFetchResultData) -> Result<String, CompilationError> {
+  let ext
+    = utils::get_extension(&fetch_data.locator.specifier);
+
+  Ok(format!("data:{};base64,{}", get_inline_mime_type(&ext), base64::engine::general_purpose::STANDARD.encode(get_source_bytes(fetch_data)?)))
+}
+
+pub fn transform_inline(_data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  match get_data_url(&fetch_data) {
+    Ok(data_url) => transform_generated_module(project, fetch_data, args, &data_url),
+    Err(err) => OnTransformResult::from_error(err),
+  }
+}
+
+pub fn transform_meta(_data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  super::mdx::transform_mdx_meta(project, fetch_data, args)
+}
+
+fn get_text(fetch_data: &OnFetchResultData) -> Result<String, CompilationError> {
+  Ok(String::from_utf8_lossy(&get_source_bytes(fetch_data)?).into_owned())
+}
+
+pub fn transform_raw(_data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  match get_text(&fetch_data) {
+    Ok(text) => transform_generated_module(project, fetch_data, args, &text),
+    Err(err) => OnTransformResult::from_error(err),
+  }
+}
+
+pub fn transform_url(_data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+  let url
+    = fetch_data.locator.without_query().url;
+
+  transform_generated_module(project, fetch_data, args, &url)
+}
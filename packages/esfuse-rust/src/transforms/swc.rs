@@ -1,30 +1,103 @@
 use std::sync::Arc;
 
-use swc::config::{SourceMapsConfig, ModuleConfig};
-use swc_common::{errors::Handler, GLOBALS, FileName, comments::SingleThreadedComments};
-use swc_core::ecma::{ast::EsVersion, parser::{Syntax, TsConfig}, visit::as_folder};
+use arca::Path;
+use swc::config::{PluginConfig, SourceMapsConfig};
+use swc_common::{chain, errors::Handler, pass::Optional, GLOBALS, FileName, comments::SingleThreadedComments};
+use swc_core::ecma::{ast::EsVersion, parser::{Syntax, TsConfig}, visit::{as_folder, Fold}};
 
 use crate::types::*;
 use crate::utils;
+use crate::utils::errors::{Diagnostic, Highlight};
 use crate::{CompilationError, Project};
 
+mod declarations;
+mod graphql_tag;
 mod visitor_1_before;
 mod visitor_2_after;
 
 #[derive(Debug, Default, Clone)]
 #[napi(object)]
 pub struct OnTransformSwcOpts {
+  pub emit_declarations: bool,
+  pub module_format: ModuleFormat,
   pub use_esfuse_runtime: bool,
   pub promisify_body: bool,
+  pub plugins: Vec<SwcPlugin>,
+  pub builtins: SwcBuiltins,
 }
 
-pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
+#[derive(Debug, Default, Clone)]
+#[napi(object)]
+pub struct SwcBuiltins {
+  pub emotion: bool,
+  pub graphql_tag: bool,
+  pub styled_components: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+#[napi(object)]
+pub struct SwcPlugin {
+  pub path: String,
+  pub options: serde_json::Value,
+  pub include: Vec<String>,
+}
+
+fn get_plugin_configs(project: &Project, locator: &ModuleLocator, plugins: &[SwcPlugin]) -> Vec<PluginConfig> {
+  let relative_path = locator.physical_path(project).map(|path| {
+    path.relative_to(&project.root)
+  });
+
+  plugins.iter().filter(|plugin| {
+    plugin.include.is_empty() || relative_path.as_ref().map_or(false, |relative_path| {
+      plugin.include.iter().any(|pattern| glob_match::glob_match(pattern.trim_start_matches("./"), relative_path.as_str()))
+    })
+  }).map(|plugin| {
+    // Relative plugin paths are resolved from the project root rather
+    // than the process working directory
+    let path = match plugin.path.starts_with('.') {
+      true => project.root.join(&Path::from(&plugin.path)).to_string(),
+      false => plugin.path.clone(),
+    };
+
+    PluginConfig(path, plugin.options.clone())
+  }).collect()
+}
+
+// Only the display names and component ids are added; the ssr flag is what
+// enables the ids, which keep the class names stable across reloads
+fn get_styled_components_pass(file_name: FileName, file_hash: u128) -> impl Fold {
+  let config: styled_components::Config = serde_json::from_value(serde_json::json!({
+    "displayName": true,
+    "ssr": true,
+    "fileName": true,
+  })).unwrap();
+
+  styled_components::styled_components(file_name, file_hash, config)
+}
+
+fn get_emotion_pass(file_path: &std::path::Path, file_hash: u128, cm: Arc<swc_common::SourceMap>, comments: SingleThreadedComments) -> impl Fold {
+  let config = swc_emotion::EmotionOptions {
+    enabled: Some(true),
+    auto_label: Some(true),
+    ..Default::default()
+  };
+
+  swc_emotion::emotion(config, file_path, file_hash as u32, cm, comments)
+}
+
+pub fn transform_swc(project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs) -> OnTransformResult {
   let cm = Arc::<swc_common::SourceMap>::default();
   let c = swc::Compiler::new(cm.clone());
 
   let mut transform_before = visitor_1_before::TransformVisitor {
   };
 
+  let builtins
+    = &args.opts.swc.builtins;
+
+  let mut transform_graphql_tag
+    = graphql_tag::TransformVisitor::default();
+
   let mut transform_after = visitor_2_after::TransformVisitor {
     opts: &args.opts.swc,
     url: fetch_data.locator.url.clone(),
@@ -42,6 +115,10 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
 
   let comments = SingleThreadedComments::default();
 
+  let file_name = file.name.clone();
+  let file_path = std::path::PathBuf::from(&fetch_data.locator.url);
+  let file_hash = file.src_hash;
+
   let mut swc_config = swc::config::Options::default();
   swc_config.source_maps = Some(SourceMapsConfig::Bool(true));
   swc_config.config.jsc.target = Some(EsVersion::Es2022);
@@ -66,9 +143,14 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
     "mangle": false
   }"#).unwrap());
 
-  swc_config.config.module = Some(ModuleConfig::CommonJs(serde_json::from_str(r#"{
-    "ignoreDynamic": true
-  }"#).unwrap()));
+  swc_config.config.module = utils::swc::get_module_config(args.opts.swc.module_format);
+
+  let plugin_configs
+    = get_plugin_configs(project, &fetch_data.locator, &args.opts.swc.plugins);
+
+  if !plugin_configs.is_empty() {
+    swc_config.config.jsc.experimental.plugins = Some(plugin_configs);
+  }
 
   swc_config.config.jsc.syntax = Some(Syntax::Typescript(TsConfig {
     tsx: true,
@@ -96,6 +178,20 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
     }
   };
 
+  let should_emit_declarations
+    = args.opts.swc.emit_declarations && declarations::is_declaration_source(&fetch_data.locator.specifier);
+
+  // Declaration errors are kept apart from the transform result, since
+  // they only matter to the builds that emit the declarations
+  let (declaration, declaration_error) = match should_emit_declarations {
+    true => match declarations::generate_declarations(&program, &fetch_data.locator.url, cm.clone()) {
+      Ok(declaration) => (Some(declaration), None),
+      Err(err) => (None, Some(err)),
+    },
+
+    false => (None, None),
+  };
+
   let transform_res = GLOBALS.set(&Default::default(), || {
     swc_common::errors::HANDLER.set(&handler, || {
       c.run_transform(&handler, true, || {
@@ -104,9 +200,14 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
           Some(program),
           &handler,
           &swc_config,
-          comments,
+          comments.clone(),
           |_| {
-            as_folder(&mut transform_before)
+            chain!(
+              as_folder(&mut transform_before),
+              Optional::new(get_styled_components_pass(file_name, file_hash), builtins.styled_components),
+              Optional::new(get_emotion_pass(&file_path, file_hash, cm.clone(), comments), builtins.emotion),
+              Optional::new(as_folder(&mut transform_graphql_tag), builtins.graphql_tag)
+            )
           },
           |_| {
             as_folder(&mut transform_after)
@@ -116,6 +217,26 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
     })
   });
 
+  // The graphql tags are parsed by our own pass, so their syntax errors
+  // don't go through the swc handler
+  if !transform_graphql_tag.errors.is_empty() {
+    return OnTransformResult {
+      result: Err(CompilationError {
+        diagnostics: transform_graphql_tag.errors.iter().map(|(message, span)| {
+          Diagnostic::from_string_with_highlight(message.clone(), Highlight {
+            source: Some(fetch_data.locator.url.clone()),
+            subject: None,
+            label: None,
+            span: Some(Span::from_swc(span, &cm)),
+          })
+        }).collect(),
+      }),
+      dependencies: vec![
+        fetch_data.locator,
+      ],
+    };
+  }
+
   match transform_res {
     Ok(output) => {
       OnTransformResult {
@@ -125,6 +246,9 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
           code: output.code,
           map: output.map,
 
+          declaration,
+          declaration_error,
+
           imports: transform_after.imports.into_iter().map(|import_swc| {
             Import {
               kind: import_swc.kind,
@@ -140,9 +264,18 @@ pub fn transform_swc(_project: &Project, fetch_data: OnFetchResultData, args: On
       }
     },
 
-    Err(_) => {
+    Err(err) => {
+      let mut compilation_error
+        = CompilationError::from_swc(&error_buffer, fetch_data.locator.url.clone(), &cm);
+
+      // Plugin failures (missing files, invalid options, panics inside the
+      // Wasm module) are reported through the error rather than the handler
+      if compilation_error.diagnostics.is_empty() {
+        compilation_error = CompilationError::from_string(format!("{:#}", err));
+      }
+
       OnTransformResult {
-        result: Err(CompilationError::from_swc(&error_buffer, fetch_data.locator.url.clone(), &cm)),
+        result: Err(compilation_error),
         dependencies: vec![
           fetch_data.locator,
         ],
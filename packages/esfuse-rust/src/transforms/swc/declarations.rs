@@ -0,0 +1,654 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use swc_common::{DUMMY_SP, Spanned};
+use swc_core::ecma::ast;
+use swc_core::ecma::codegen::{text_writer::JsWriter, Emitter};
+use swc_core::ecma::visit::{Visit, VisitWith};
+
+use crate::types::*;
+use crate::utils::errors::{Diagnostic, Highlight};
+use crate::CompilationError;
+
+pub fn is_declaration_source(specifier: &str) -> bool {
+  let is_ts = specifier.ends_with(".ts") || specifier.ends_with(".tsx") || specifier.ends_with(".mts") || specifier.ends_with(".cts");
+  let is_dts = specifier.ends_with(".d.ts") || specifier.ends_with(".d.mts") || specifier.ends_with(".d.cts");
+
+  is_ts && !is_dts
+}
+
+// Collects the names of the declarations referenced from type positions,
+// so that the local declarations they point to can be emitted as well
+#[derive(Default)]
+struct ReferenceCollector {
+  references: HashSet<String>,
+}
+
+fn get_leftmost_ident(expr: &ast::Expr) -> Option<&ast::Ident> {
+  match expr {
+    ast::Expr::Ident(ident) => Some(ident),
+    ast::Expr::Member(member) => get_leftmost_ident(&member.obj),
+    _ => None,
+  }
+}
+
+impl Visit for ReferenceCollector {
+  fn visit_ts_entity_name(&mut self, name: &ast::TsEntityName) {
+    match name {
+      ast::TsEntityName::Ident(ident) => {
+        self.references.insert(ident.sym.to_string());
+      },
+
+      ast::TsEntityName::TsQualifiedName(qualified_name) => {
+        qualified_name.left.visit_with(self);
+      },
+    }
+  }
+
+  fn visit_ts_expr_with_type_args(&mut self, node: &ast::TsExprWithTypeArgs) {
+    if let Some(ident) = get_leftmost_ident(&node.expr) {
+      self.references.insert(ident.sym.to_string());
+    }
+
+    node.visit_children_with(self);
+  }
+
+  fn visit_class(&mut self, class: &ast::Class) {
+    if let Some(ident) = class.super_class.as_deref().and_then(get_leftmost_ident) {
+      self.references.insert(ident.sym.to_string());
+    }
+
+    class.visit_children_with(self);
+  }
+}
+
+fn get_decl_names(decl: &ast::Decl) -> Vec<String> {
+  match decl {
+    ast::Decl::Class(class) => vec![class.ident.sym.to_string()],
+    ast::Decl::Fn(function) => vec![function.ident.sym.to_string()],
+    ast::Decl::Var(var) => var.decls.iter().filter_map(|declarator| match &declarator.name {
+      ast::Pat::Ident(binding) => Some(binding.id.sym.to_string()),
+      _ => None,
+    }).collect(),
+    ast::Decl::TsInterface(interface) => vec![interface.id.sym.to_string()],
+    ast::Decl::TsTypeAlias(alias) => vec![alias.id.sym.to_string()],
+    ast::Decl::TsEnum(enum_decl) => vec![enum_decl.id.sym.to_string()],
+    ast::Decl::TsModule(module_decl) => match (&module_decl.id, module_decl.global) {
+      (ast::TsModuleName::Ident(ident), false) => vec![ident.sym.to_string()],
+      _ => vec![],
+    },
+  }
+}
+
+struct DeclarationGenerator<'a> {
+  url: &'a str,
+  cm: &'a swc_common::SourceMap,
+  diagnostics: Vec<Diagnostic>,
+  exported_locals: HashSet<String>,
+}
+
+fn keyword_type_ann(kind: ast::TsKeywordTypeKind) -> Box<ast::TsTypeAnn> {
+  Box::new(ast::TsTypeAnn {
+    span: DUMMY_SP,
+    type_ann: Box::new(ast::TsType::TsKeywordType(ast::TsKeywordType {
+      span: DUMMY_SP,
+      kind,
+    })),
+  })
+}
+
+fn infer_literal_type(expr: &ast::Expr) -> Option<Box<ast::TsTypeAnn>> {
+  match expr {
+    ast::Expr::Lit(ast::Lit::Str(_)) => Some(keyword_type_ann(ast::TsKeywordTypeKind::TsStringKeyword)),
+    ast::Expr::Lit(ast::Lit::Num(_)) => Some(keyword_type_ann(ast::TsKeywordTypeKind::TsNumberKeyword)),
+    ast::Expr::Lit(ast::Lit::Bool(_)) => Some(keyword_type_ann(ast::TsKeywordTypeKind::TsBooleanKeyword)),
+    ast::Expr::Lit(ast::Lit::BigInt(_)) => Some(keyword_type_ann(ast::TsKeywordTypeKind::TsBigIntKeyword)),
+    ast::Expr::Tpl(_) => Some(keyword_type_ann(ast::TsKeywordTypeKind::TsStringKeyword)),
+    ast::Expr::Unary(ast::UnaryExpr {op: ast::UnaryOp::Minus, arg, ..}) => infer_literal_type(arg),
+    _ => None,
+  }
+}
+
+// Only string and numeric literals are allowed as initializers for
+// constants in ambient contexts
+fn is_ambient_initializer(expr: &ast::Expr) -> bool {
+  match expr {
+    ast::Expr::Lit(ast::Lit::Str(_)) | ast::Expr::Lit(ast::Lit::Num(_)) => true,
+    ast::Expr::Unary(ast::UnaryExpr {op: ast::UnaryOp::Minus, arg, ..}) => matches!(&**arg, ast::Expr::Lit(ast::Lit::Num(_))),
+    _ => false,
+  }
+}
+
+fn is_private_member(accessibility: Option<ast::Accessibility>) -> bool {
+  matches!(accessibility, Some(ast::Accessibility::Private))
+}
+
+impl<'a> DeclarationGenerator<'a> {
+  fn report(&mut self, message: &str, span: swc_common::Span) {
+    self.diagnostics.push(Diagnostic::from_str_with_highlight(message, Highlight {
+      source: Some(self.url.to_string()),
+      subject: None,
+      label: None,
+      span: Some(Span::from_swc(&span, self.cm)),
+    }));
+  }
+
+  fn collect_exported_locals(&mut self, module: &ast::Module) {
+    for item in &module.body {
+      match item {
+        ast::ModuleItem::ModuleDecl(ast::ModuleDecl::ExportNamed(export)) if export.src.is_none() => {
+          for specifier in &export.specifiers {
+            if let ast::ExportSpecifier::Named(ast::ExportNamedSpecifier {orig: ast::ModuleExportName::Ident(ident), ..}) = specifier {
+              self.exported_locals.insert(ident.sym.to_string());
+            }
+          }
+        },
+
+        ast::ModuleItem::ModuleDecl(ast::ModuleDecl::ExportDefaultExpr(export)) => {
+          if let ast::Expr::Ident(ident) = &*export.expr {
+            self.exported_locals.insert(ident.sym.to_string());
+          }
+        },
+
+        _ => {},
+      }
+    }
+  }
+
+  fn is_exported_local(&self, decl: &ast::Decl) -> bool {
+    let names
+      = get_decl_names(decl);
+
+    // Ambient modules and global augmentations don't have a local name;
+    // they apply regardless of what the module exports
+    names.is_empty() || names.iter().any(|name| self.exported_locals.contains(name))
+  }
+
+  fn process_module(&mut self, module: &ast::Module) -> ast::Module {
+    self.collect_exported_locals(module);
+
+    let mut items = vec![];
+    let mut overloaded_functions = HashSet::new();
+
+    // Declarations that aren't exported are only emitted if one of the
+    // emitted declarations references them
+    let mut local_items: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (index, item) in module.body.iter().enumerate() {
+      // Implementations that follow overload signatures are omitted
+      // from the declarations, as tsc does
+      let function = match item {
+        ast::ModuleItem::Stmt(ast::Stmt::Decl(ast::Decl::Fn(function))) => Some(function),
+        ast::ModuleItem::ModuleDecl(ast::ModuleDecl::ExportDecl(ast::ExportDecl {decl: ast::Decl::Fn(function), ..})) => Some(function),
+        _ => None,
+      };
+
+      if let Some(function) = function {
+        let name = function.ident.sym.to_string();
+
+        match function.function.body.is_none() {
+          true => {
+            overloaded_functions.insert(name);
+          },
+
+          false => if overloaded_functions.contains(&name) {
+            continue;
+          },
+        }
+      }
+
+      if let ast::ModuleItem::Stmt(ast::Stmt::Decl(decl)) = item {
+        if !self.is_exported_local(decl) {
+          for name in get_decl_names(decl) {
+            local_items.entry(name).or_default().push(index);
+          }
+
+          items.push(None);
+          continue;
+        }
+      }
+
+      items.push(self.process_module_item(item));
+    }
+
+    let mut collector = ReferenceCollector::default();
+    for item in items.iter().flatten() {
+      item.visit_with(&mut collector);
+    }
+
+    let mut queue: Vec<String>
+      = collector.references.into_iter().collect();
+
+    let mut has_local_items = false;
+
+    while let Some(name) = queue.pop() {
+      for index in local_items.remove(&name).unwrap_or_default() {
+        let decl = match &module.body[index] {
+          ast::ModuleItem::Stmt(ast::Stmt::Decl(decl)) if items[index].is_none() => decl,
+          _ => continue,
+        };
+
+        if let Some(decl) = self.process_decl(decl) {
+          let mut collector = ReferenceCollector::default();
+          decl.visit_with(&mut collector);
+
+          queue.extend(collector.references);
+
+          items[index] = Some(ast::ModuleItem::Stmt(ast::Stmt::Decl(decl)));
+          has_local_items = true;
+        }
+      }
+    }
+
+    let mut body: Vec<ast::ModuleItem>
+      = items.into_iter().flatten().collect();
+
+    // Declaration files implicitly export all their top-level declarations
+    // unless they contain an export clause, as tsc generates
+    if has_local_items {
+      body.push(ast::ModuleItem::ModuleDecl(ast::ModuleDecl::ExportNamed(ast::NamedExport {
+        span: DUMMY_SP,
+        specifiers: vec![],
+        src: None,
+        type_only: false,
+        asserts: None,
+      })));
+    }
+
+    ast::Module {
+      span: module.span,
+      body,
+      shebang: None,
+    }
+  }
+
+  fn process_module_item(&mut self, item: &ast::ModuleItem) -> Option<ast::ModuleItem> {
+    match item {
+      ast::ModuleItem::ModuleDecl(module_decl) => {
+        self.process_module_decl(module_decl).map(ast::ModuleItem::ModuleDecl)
+      },
+
+      ast::ModuleItem::Stmt(ast::Stmt::Decl(decl)) => {
+        self.process_decl(decl)
+          .map(|decl| ast::ModuleItem::Stmt(ast::Stmt::Decl(decl)))
+      },
+
+      ast::ModuleItem::Stmt(_) => {
+        None
+      },
+    }
+  }
+
+  fn process_module_decl(&mut self, module_decl: &ast::ModuleDecl) -> Option<ast::ModuleDecl> {
+    match module_decl {
+      ast::ModuleDecl::ExportDecl(export) => {
+        self.process_decl(&export.decl).map(|decl| ast::ModuleDecl::ExportDecl(ast::ExportDecl {
+          span: export.span,
+          decl,
+        }))
+      },
+
+      ast::ModuleDecl::ExportDefaultDecl(export) => {
+        let decl = match &export.decl {
+          ast::DefaultDecl::Class(class_expr) => {
+            let mut class = class_expr.class.clone();
+            self.process_class(&mut class);
+
+            ast::DefaultDecl::Class(ast::ClassExpr {
+              ident: class_expr.ident.clone(),
+              class,
+            })
+          },
+
+          ast::DefaultDecl::Fn(fn_expr) => {
+            let mut function = fn_expr.function.clone();
+            self.process_function(&mut function, "Function");
+
+            ast::DefaultDecl::Fn(ast::FnExpr {
+              ident: fn_expr.ident.clone(),
+              function,
+            })
+          },
+
+          ast::DefaultDecl::TsInterfaceDecl(_) => {
+            export.decl.clone()
+          },
+        };
+
+        Some(ast::ModuleDecl::ExportDefaultDecl(ast::ExportDefaultDecl {
+          span: export.span,
+          decl,
+        }))
+      },
+
+      ast::ModuleDecl::ExportDefaultExpr(export) => {
+        match &*export.expr {
+          ast::Expr::Ident(_) => {
+            Some(module_decl.clone())
+          },
+
+          _ => {
+            self.report("Default exports must reference a declaration for their type to be emitted", export.span);
+            None
+          },
+        }
+      },
+
+      _ => {
+        Some(module_decl.clone())
+      },
+    }
+  }
+
+  fn process_decl(&mut self, decl: &ast::Decl) -> Option<ast::Decl> {
+    match decl {
+      ast::Decl::Class(class_decl) => {
+        let mut class = class_decl.class.clone();
+        self.process_class(&mut class);
+
+        Some(ast::Decl::Class(ast::ClassDecl {
+          ident: class_decl.ident.clone(),
+          declare: true,
+          class,
+        }))
+      },
+
+      ast::Decl::Fn(fn_decl) => {
+        let mut function = fn_decl.function.clone();
+        self.process_function(&mut function, "Function");
+
+        Some(ast::Decl::Fn(ast::FnDecl {
+          ident: fn_decl.ident.clone(),
+          declare: true,
+          function,
+        }))
+      },
+
+      ast::Decl::Var(var_decl) => {
+        let mut var_decl = var_decl.clone();
+        var_decl.declare = true;
+
+        let kind = var_decl.kind;
+        for declarator in &mut var_decl.decls {
+          self.process_var_declarator(declarator, kind);
+        }
+
+        Some(ast::Decl::Var(var_decl))
+      },
+
+      ast::Decl::TsEnum(enum_decl) => {
+        let mut enum_decl = enum_decl.clone();
+        enum_decl.declare = true;
+
+        Some(ast::Decl::TsEnum(enum_decl))
+      },
+
+      ast::Decl::TsModule(module_decl) => {
+        let mut module_decl = module_decl.clone();
+        module_decl.declare = true;
+
+        Some(ast::Decl::TsModule(module_decl))
+      },
+
+      ast::Decl::TsInterface(_) | ast::Decl::TsTypeAlias(_) => {
+        Some(decl.clone())
+      },
+
+      _ => {
+        None
+      },
+    }
+  }
+
+  fn process_var_declarator(&mut self, declarator: &mut ast::VarDeclarator, kind: ast::VarDeclKind) {
+    let init = declarator.init.take();
+
+    let binding = match &mut declarator.name {
+      ast::Pat::Ident(binding) => binding,
+      _ => {
+        self.report("Destructured variables can't be exported without an explicit type annotation", declarator.span);
+        return;
+      },
+    };
+
+    if binding.type_ann.is_some() {
+      return;
+    }
+
+    match init {
+      Some(init) if kind == ast::VarDeclKind::Const && is_ambient_initializer(&init) => {
+        declarator.init = Some(init);
+      },
+
+      Some(init) if infer_literal_type(&init).is_some() => {
+        binding.type_ann = infer_literal_type(&init);
+      },
+
+      _ => {
+        self.report("Variable must have an explicit type annotation", declarator.span);
+      },
+    }
+  }
+
+  fn process_function(&mut self, function: &mut ast::Function, kind: &str) {
+    if function.return_type.is_none() {
+      self.report(&format!("{} must have an explicit return type annotation", kind), function.span);
+    }
+
+    for param in &mut function.params {
+      param.decorators.clear();
+      self.process_param_pat(&mut param.pat);
+    }
+
+    function.decorators.clear();
+    function.body = None;
+
+    // Those modifiers aren't allowed in ambient contexts; the return
+    // type annotation is enough to describe the function
+    function.is_async = false;
+    function.is_generator = false;
+  }
+
+  fn process_param_pat(&mut self, pat: &mut ast::Pat) {
+    let is_annotated = match pat {
+      ast::Pat::Ident(binding) => binding.type_ann.is_some(),
+      ast::Pat::Rest(rest) => rest.type_ann.is_some(),
+      ast::Pat::Object(object) => object.type_ann.is_some(),
+      ast::Pat::Array(array) => array.type_ann.is_some(),
+
+      // Parameters with a default value become optional parameters
+      ast::Pat::Assign(assign) => {
+        let mut left = (*assign.left).clone();
+
+        match &mut left {
+          ast::Pat::Ident(binding) => {
+            binding.id.optional = true;
+
+            if binding.type_ann.is_none() {
+              binding.type_ann = infer_literal_type(&assign.right);
+            }
+          },
+
+          ast::Pat::Object(object) => {
+            object.optional = true;
+          },
+
+          ast::Pat::Array(array) => {
+            array.optional = true;
+          },
+
+          _ => {},
+        }
+
+        *pat = left;
+        return self.process_param_pat(pat);
+      },
+
+      _ => false,
+    };
+
+    if !is_annotated {
+      self.report("Parameter must have an explicit type annotation", pat.span());
+    }
+  }
+
+  fn process_class(&mut self, class: &mut ast::Class) {
+    class.decorators.clear();
+
+    let mut body = vec![];
+
+    for member in class.body.drain(..) {
+      match member {
+        ast::ClassMember::Constructor(mut constructor) => {
+          let mut param_props = vec![];
+
+          for param in &mut constructor.params {
+            match param {
+              ast::ParamOrTsParamProp::Param(param) => {
+                param.decorators.clear();
+                self.process_param_pat(&mut param.pat);
+              },
+
+              // Parameter properties aren't allowed in declarations; we
+              // turn them into regular properties
+              ast::ParamOrTsParamProp::TsParamProp(param_prop) => {
+                let mut pat = match &param_prop.param {
+                  ast::TsParamPropParam::Ident(binding) => ast::Pat::Ident(binding.clone()),
+                  ast::TsParamPropParam::Assign(assign) => ast::Pat::Assign(assign.clone()),
+                };
+
+                self.process_param_pat(&mut pat);
+
+                if let ast::Pat::Ident(binding) = &pat {
+                  param_props.push(ast::ClassMember::ClassProp(ast::ClassProp {
+                    span: param_prop.span,
+                    key: ast::PropName::Ident(ast::Ident::new(binding.id.sym.clone(), binding.id.span)),
+                    value: None,
+                    type_ann: match is_private_member(param_prop.accessibility) {
+                      true => None,
+                      false => binding.type_ann.clone(),
+                    },
+                    is_static: false,
+                    decorators: vec![],
+                    accessibility: param_prop.accessibility,
+                    is_abstract: false,
+                    is_optional: binding.id.optional,
+                    is_override: param_prop.is_override,
+                    readonly: param_prop.readonly,
+                    declare: false,
+                    definite: false,
+                  }));
+                }
+
+                *param = ast::ParamOrTsParamProp::Param(ast::Param {
+                  span: param_prop.span,
+                  decorators: vec![],
+                  pat,
+                });
+              },
+            }
+          }
+
+          constructor.body = None;
+
+          body.append(&mut param_props);
+          body.push(ast::ClassMember::Constructor(constructor));
+        },
+
+        ast::ClassMember::Method(mut method) => {
+          if is_private_member(method.accessibility) {
+            continue;
+          }
+
+          match method.kind {
+            ast::MethodKind::Setter => {
+              for param in &mut method.function.params {
+                self.process_param_pat(&mut param.pat);
+              }
+
+              method.function.decorators.clear();
+              method.function.body = None;
+            },
+
+            _ => {
+              self.process_function(&mut method.function, "Method");
+            },
+          }
+
+          body.push(ast::ClassMember::Method(method));
+        },
+
+        ast::ClassMember::ClassProp(mut prop) => {
+          let value = prop.value.take();
+
+          if !is_private_member(prop.accessibility) && prop.type_ann.is_none() {
+            prop.type_ann = value.as_deref().and_then(infer_literal_type);
+
+            if prop.type_ann.is_none() {
+              self.report("Property must have an explicit type annotation", prop.span);
+            }
+          }
+
+          if is_private_member(prop.accessibility) {
+            prop.type_ann = None;
+          }
+
+          prop.decorators.clear();
+          body.push(ast::ClassMember::ClassProp(prop));
+        },
+
+        ast::ClassMember::TsIndexSignature(_) => {
+          body.push(member);
+        },
+
+        // ECMAScript private members, static blocks, etc don't
+        // contribute to the public shape of the class
+        _ => {},
+      }
+    }
+
+    class.body = body;
+  }
+}
+
+fn print_module(module: &ast::Module, cm: Arc<swc_common::SourceMap>) -> Result<String, CompilationError> {
+  let mut buf = vec![];
+
+  {
+    let mut emitter = Emitter {
+      cfg: Default::default(),
+      cm: cm.clone(),
+      comments: None,
+      wr: JsWriter::new(cm, "\n", &mut buf, None),
+    };
+
+    emitter.emit_module(module).map_err(CompilationError::from_err)?;
+  }
+
+  String::from_utf8(buf).map_err(CompilationError::from_err)
+}
+
+pub fn generate_declarations(program: &ast::Program, url: &str, cm: Arc<swc_common::SourceMap>) -> Result<String, CompilationError> {
+  let module = match program {
+    ast::Program::Module(module) => module,
+    ast::Program::Script(_) => return Err(CompilationError::from_str("Declarations can only be generated for modules")),
+  };
+
+  let mut generator = DeclarationGenerator {
+    url,
+    cm: &cm,
+    diagnostics: vec![],
+    exported_locals: HashSet::new(),
+  };
+
+  let declarations
+    = generator.process_module(module);
+
+  if !generator.diagnostics.is_empty() {
+    return Err(CompilationError {
+      diagnostics: generator.diagnostics,
+    });
+  }
+
+  print_module(&declarations, cm)
+}
@@ -0,0 +1,290 @@
+use graphql_parser::query;
+use serde_json::{json, Value};
+use swc_core::common::{Span, DUMMY_SP};
+use swc_core::ecma::ast;
+use swc_core::ecma::utils::{quote_ident, quote_str};
+use swc_core::ecma::visit::{VisitMut, VisitMutWith};
+
+const TAG_SOURCES: &[(&str, Option<&str>)] = &[
+  ("graphql-tag", None),
+  ("graphql-tag", Some("gql")),
+  ("@apollo/client", Some("gql")),
+];
+
+#[derive(Default)]
+pub struct TransformVisitor {
+  pub errors: Vec<(String, Span)>,
+  bindings: Vec<String>,
+}
+
+fn is_tag_import(source: &str, specifier: &ast::ImportSpecifier) -> bool {
+  let imported = match specifier {
+    ast::ImportSpecifier::Default(_) => None,
+    ast::ImportSpecifier::Named(named) => Some(match &named.imported {
+      Some(ast::ModuleExportName::Ident(ident)) => ident.sym.to_string(),
+      Some(ast::ModuleExportName::Str(str)) => str.value.to_string(),
+      None => named.local.sym.to_string(),
+    }),
+    ast::ImportSpecifier::Namespace(_) => return false,
+  };
+
+  TAG_SOURCES.iter().any(|(tag_source, tag_name)| {
+    *tag_source == source && tag_name.map(str::to_string) == imported
+  })
+}
+
+fn get_local_name(specifier: &ast::ImportSpecifier) -> String {
+  match specifier {
+    ast::ImportSpecifier::Default(default) => default.local.sym.to_string(),
+    ast::ImportSpecifier::Named(named) => named.local.sym.to_string(),
+    ast::ImportSpecifier::Namespace(namespace) => namespace.local.sym.to_string(),
+  }
+}
+
+// The output follows the graphql-js AST format, so that it can be passed to
+// Apollo & co. as if graphql-tag had parsed it; the parser doesn't keep the
+// raw number literals nor the block string flags, so those are normalized
+
+fn make_name(value: &str) -> Value {
+  json!({"kind": "Name", "value": value})
+}
+
+fn make_named_type(name: &str) -> Value {
+  json!({"kind": "NamedType", "name": make_name(name)})
+}
+
+fn convert_type(ty: &query::Type<String>) -> Value {
+  match ty {
+    query::Type::NamedType(name) => make_named_type(name),
+    query::Type::ListType(ty) => json!({"kind": "ListType", "type": convert_type(ty)}),
+    query::Type::NonNullType(ty) => json!({"kind": "NonNullType", "type": convert_type(ty)}),
+  }
+}
+
+fn convert_value(value: &query::Value<String>) -> Value {
+  match value {
+    query::Value::Variable(name) => json!({"kind": "Variable", "name": make_name(name)}),
+    query::Value::Int(number) => json!({"kind": "IntValue", "value": number.as_i64().unwrap_or_default().to_string()}),
+    query::Value::Float(number) => json!({"kind": "FloatValue", "value": number.to_string()}),
+    query::Value::String(value) => json!({"kind": "StringValue", "value": value, "block": false}),
+    query::Value::Boolean(value) => json!({"kind": "BooleanValue", "value": value}),
+    query::Value::Null => json!({"kind": "NullValue"}),
+    query::Value::Enum(value) => json!({"kind": "EnumValue", "value": value}),
+    query::Value::List(values) => json!({"kind": "ListValue", "values": values.iter().map(convert_value).collect::<Vec<_>>()}),
+    query::Value::Object(fields) => json!({"kind": "ObjectValue", "fields": fields.iter().map(|(name, value)| {
+      json!({"kind": "ObjectField", "name": make_name(name), "value": convert_value(value)})
+    }).collect::<Vec<_>>()}),
+  }
+}
+
+fn convert_arguments(arguments: &[(String, query::Value<String>)]) -> Vec<Value> {
+  arguments.iter().map(|(name, value)| {
+    json!({"kind": "Argument", "name": make_name(name), "value": convert_value(value)})
+  }).collect()
+}
+
+fn convert_directives(directives: &[query::Directive<String>]) -> Vec<Value> {
+  directives.iter().map(|directive| {
+    json!({"kind": "Directive", "name": make_name(&directive.name), "arguments": convert_arguments(&directive.arguments)})
+  }).collect()
+}
+
+fn convert_type_condition(type_condition: &query::TypeCondition<String>) -> Value {
+  let query::TypeCondition::On(name) = type_condition;
+  make_named_type(name)
+}
+
+fn convert_selection_set(selection_set: &query::SelectionSet<String>) -> Value {
+  let selections = selection_set.items.iter().map(|selection| match selection {
+    query::Selection::Field(field) => {
+      let mut value = json!({
+        "kind": "Field",
+        "name": make_name(&field.name),
+        "arguments": convert_arguments(&field.arguments),
+        "directives": convert_directives(&field.directives),
+      });
+
+      if let Some(alias) = &field.alias {
+        value["alias"] = make_name(alias);
+      }
+
+      if !field.selection_set.items.is_empty() {
+        value["selectionSet"] = convert_selection_set(&field.selection_set);
+      }
+
+      value
+    },
+
+    query::Selection::FragmentSpread(spread) => {
+      json!({"kind": "FragmentSpread", "name": make_name(&spread.fragment_name), "directives": convert_directives(&spread.directives)})
+    },
+
+    query::Selection::InlineFragment(fragment) => {
+      let mut value = json!({
+        "kind": "InlineFragment",
+        "directives": convert_directives(&fragment.directives),
+        "selectionSet": convert_selection_set(&fragment.selection_set),
+      });
+
+      if let Some(type_condition) = &fragment.type_condition {
+        value["typeCondition"] = convert_type_condition(type_condition);
+      }
+
+      value
+    },
+  }).collect::<Vec<_>>();
+
+  json!({"kind": "SelectionSet", "selections": selections})
+}
+
+fn convert_operation(operation: &str, name: &Option<String>, variable_definitions: &[query::VariableDefinition<String>], directives: &[query::Directive<String>], selection_set: &query::SelectionSet<String>) -> Value {
+  let variable_definitions = variable_definitions.iter().map(|definition| {
+    let mut value = json!({
+      "kind": "VariableDefinition",
+      "variable": {"kind": "Variable", "name": make_name(&definition.name)},
+      "type": convert_type(&definition.var_type),
+      "directives": [],
+    });
+
+    if let Some(default_value) = &definition.default_value {
+      value["defaultValue"] = convert_value(default_value);
+    }
+
+    value
+  }).collect::<Vec<_>>();
+
+  let mut value = json!({
+    "kind": "OperationDefinition",
+    "operation": operation,
+    "variableDefinitions": variable_definitions,
+    "directives": convert_directives(directives),
+    "selectionSet": convert_selection_set(selection_set),
+  });
+
+  if let Some(name) = name {
+    value["name"] = make_name(name);
+  }
+
+  value
+}
+
+fn convert_definition(definition: &query::Definition<String>) -> Value {
+  match definition {
+    query::Definition::Operation(query::OperationDefinition::SelectionSet(selection_set))
+      => convert_operation("query", &None, &[], &[], selection_set),
+    query::Definition::Operation(query::OperationDefinition::Query(query))
+      => convert_operation("query", &query.name, &query.variable_definitions, &query.directives, &query.selection_set),
+    query::Definition::Operation(query::OperationDefinition::Mutation(mutation))
+      => convert_operation("mutation", &mutation.name, &mutation.variable_definitions, &mutation.directives, &mutation.selection_set),
+    query::Definition::Operation(query::OperationDefinition::Subscription(subscription))
+      => convert_operation("subscription", &subscription.name, &subscription.variable_definitions, &subscription.directives, &subscription.selection_set),
+
+    query::Definition::Fragment(fragment) => json!({
+      "kind": "FragmentDefinition",
+      "name": make_name(&fragment.name),
+      "typeCondition": convert_type_condition(&fragment.type_condition),
+      "directives": convert_directives(&fragment.directives),
+      "selectionSet": convert_selection_set(&fragment.selection_set),
+    }),
+  }
+}
+
+// Returns None for documents containing type system definitions, which we
+// leave to graphql-tag
+fn parse_document(source: &str) -> Result<Option<Value>, String> {
+  let document = match query::parse_query::<String>(source) {
+    Ok(document) => document,
+    Err(_) if graphql_parser::parse_schema::<String>(source).is_ok() => return Ok(None),
+    Err(err) => return Err(format!("Syntax Error: {}", err)),
+  };
+
+  let definitions = document.definitions.iter()
+    .map(convert_definition)
+    .collect::<Vec<_>>();
+
+  // graphql-tag strips the locations from every node except the document
+  Ok(Some(json!({
+    "kind": "Document",
+    "definitions": definitions,
+    "loc": {
+      "start": 0,
+      "end": source.encode_utf16().count(),
+      "source": {
+        "body": source,
+        "name": "GraphQL request",
+        "locationOffset": {"line": 1, "column": 1},
+      },
+    },
+  })))
+}
+
+impl VisitMut for TransformVisitor {
+  fn visit_mut_module(&mut self, n: &mut ast::Module) {
+    for item in &n.body {
+      if let ast::ModuleItem::ModuleDecl(ast::ModuleDecl::Import(import)) = item {
+        for specifier in &import.specifiers {
+          if is_tag_import(&import.src.value, specifier) {
+            self.bindings.push(get_local_name(specifier));
+          }
+        }
+      }
+    }
+
+    if self.bindings.is_empty() {
+      return;
+    }
+
+    n.visit_mut_children_with(self);
+  }
+
+  fn visit_mut_expr(&mut self, e: &mut ast::Expr) {
+    e.visit_mut_children_with(self);
+
+    let tpl = match &*e {
+      ast::Expr::TaggedTpl(tpl) => tpl,
+      _ => return,
+    };
+
+    let is_graphql_tag = match &*tpl.tag {
+      ast::Expr::Ident(ident) => self.bindings.iter().any(|binding| *binding == *ident.sym),
+      _ => false,
+    };
+
+    // Interpolated fragments are only known at runtime, so we leave those
+    // templates to graphql-tag
+    if !is_graphql_tag || !tpl.tpl.exprs.is_empty() {
+      return;
+    }
+
+    let span = tpl.span;
+
+    let source = tpl.tpl.quasis.iter()
+      .map(|quasi| quasi.cooked.as_ref().unwrap_or(&quasi.raw).to_string())
+      .collect::<String>();
+
+    let document = match parse_document(&source) {
+      Ok(Some(document)) => document,
+      Ok(None) => return,
+      Err(err) => {
+        self.errors.push((err, span));
+        return;
+      },
+    };
+
+    let json
+      = document.to_string();
+
+    *e = ast::Expr::Call(ast::CallExpr {
+      span,
+      callee: ast::Callee::Expr(Box::new(ast::Expr::Member(ast::MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(ast::Expr::Ident(quote_ident!("JSON"))),
+        prop: ast::MemberProp::Ident(quote_ident!("parse")),
+      }))),
+      args: vec![
+        ast::Expr::Lit(ast::Lit::Str(quote_str!(json))).into(),
+      ],
+      type_args: None,
+    });
+  }
+}
@@ -32,6 +32,12 @@ impl<'a> VisitMut for TransformVisitor<'a> {
   fn visit_mut_module(&mut self, e: &mut ast::Module) {
     e.visit_mut_children_with(self);
 
+    // ES modules keep their import and export statements at the top-level,
+    // so we mustn't wrap them into a function body
+    if !self.opts.promisify_body && !self.opts.use_esfuse_runtime {
+      return;
+    }
+
     let mut stmts: Vec<ast::Stmt> = e.body.clone().into_iter().filter_map(|i| match i {
       ast::ModuleItem::Stmt(stmt) => Some(stmt),
       _ => None
@@ -126,6 +132,20 @@ impl<'a> VisitMut for TransformVisitor<'a> {
     }).collect();
   }
 
+  fn visit_mut_import_decl(&mut self, n: &mut ast::ImportDecl) {
+    self.register_import(ResolutionKind::ImportDeclaration, n.src.value.to_string(), n.src.span);
+  }
+
+  fn visit_mut_export_all(&mut self, n: &mut ast::ExportAll) {
+    self.register_import(ResolutionKind::ImportDeclaration, n.src.value.to_string(), n.src.span);
+  }
+
+  fn visit_mut_named_export(&mut self, n: &mut ast::NamedExport) {
+    if let Some(src) = &n.src {
+      self.register_import(ResolutionKind::ImportDeclaration, src.value.to_string(), src.span);
+    }
+  }
+
   fn visit_mut_try_stmt(&mut self, n: &mut ast::TryStmt) {
     if n.handler.is_some() {
       self.try_stack += 1;
@@ -176,7 +196,10 @@ impl<'a> VisitMut for TransformVisitor<'a> {
     if e.callee.is_import() {
       if let Some((specifier, span)) = utils::swc::require_param_to_specifier(&e.args[0].expr) {
         self.register_import(ResolutionKind::DynamicImport, specifier, span);
-        e.callee = ast::Callee::Expr(quote_expr!("require.import"));
+
+        if self.opts.module_format == ModuleFormat::CommonJs {
+          e.callee = ast::Callee::Expr(quote_expr!("require.import"));
+        }
       }
     }
 
@@ -10,8 +10,9 @@ use std::sync::Arc;
 
 use crate::CompilationError;
 use crate::Project;
-use crate::transforms::OnTransformSwcOpts;
+use crate::transforms::{OnTransformSwcOpts, SwcBuiltins, SwcPlugin};
 use crate::utils;
+use crate::utils::cancellation::CancellationToken;
 
 #[derive(Debug)]
 #[napi]
@@ -20,6 +21,14 @@ pub enum ResolutionKind {
   DynamicImport,
 }
 
+#[derive(Debug, Default, Eq, PartialEq)]
+#[napi]
+pub enum ModuleFormat {
+  #[default]
+  CommonJs,
+  Esm,
+}
+
 #[derive(Debug, Default, Clone)]
 #[napi(object)]
 pub struct OnResolveOpts {
@@ -116,9 +125,28 @@ pub struct OnTransformResultData {
   pub code: String,
   pub map: Option<String>,
 
+  pub declaration: Option<String>,
+  pub declaration_error: Option<CompilationError>,
+
   pub imports: Vec<Import>,
 }
 
+#[derive(Debug)]
+#[napi]
+pub enum TransformStage {
+  Before,
+  After,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnTransformHookArgs {
+  pub locator: ModuleLocator,
+  pub stage: TransformStage,
+  pub data: OnTransformResultData,
+  pub opts: OnTransformOpts,
+}
+
 #[derive(Debug, Clone)]
 pub struct OnTransformResult {
   pub result: Result<OnTransformResultData, CompilationError>,
@@ -128,9 +156,15 @@ pub struct OnTransformResult {
 #[derive(Debug, Default, Clone)]
 #[napi(object)]
 pub struct OnBatchOpts {
+  pub concurrency: Option<u32>,
+  pub emit_declarations: bool,
   pub generated_module_folder: Option<Path>,
+  pub hash_filenames: bool,
+  pub module_format: ModuleFormat,
   pub pin_resolutions: bool,
   pub promisify_entry_point: bool,
+  pub swc_builtins: SwcBuiltins,
+  pub swc_plugins: Vec<SwcPlugin>,
   pub use_esfuse_runtime: bool,
   pub user_data: Arc<serde_json::Value>,
   pub traverse_dependencies: bool,
@@ -156,10 +190,13 @@ pub struct OnBatchModule {
   pub map: Option<SourceMap>,
   pub newlines: usize,
   pub resolutions: HashMap<String, Option<ModuleLocator>>,
+
+  pub declaration: Option<String>,
+  pub declaration_error: Option<CompilationError>,
 }
 
 impl OnBatchModule {
-  pub fn new(transform: OnTransformResultData, resolutions: HashMap<String, Option<ModuleLocator>>) -> Self {
+  pub fn new(transform: OnTransformResultData, resolutions: HashMap<String, Option<ModuleLocator>>) -> Result<Self, CompilationError> {
     let mut module = Self {
       mime_type: transform.mime_type,
       code: String::new(),
@@ -167,11 +204,14 @@ impl OnBatchModule {
       newlines: 0,
       resolutions,
       imaginary_path: None,
+      declaration: transform.declaration,
+      declaration_error: transform.declaration_error,
     };
 
     module.set_code(transform.code);
-    module.set_map(transform.map);
-    module
+    module.set_map(transform.map)?;
+
+    Ok(module)
   }
 
   pub fn set_code(&mut self, code: String) {
@@ -179,29 +219,83 @@ impl OnBatchModule {
     self.newlines = count_newlines(&self.code);
   }
 
-  pub fn set_map(&mut self, map_opt: Option<String>) {
-    self.map = map_opt.map(|str| {
-      parcel_sourcemap::SourceMap::from_json("/", str.as_ref())
-        .expect("Assertion failed: Expected the SWC-generated sourcemap to be readable")
-    });
+  // The maps may come from the transform hooks, so we can't assume
+  // they're valid
+  pub fn set_map(&mut self, map_opt: Option<String>) -> Result<(), CompilationError> {
+    self.map = match map_opt {
+      Some(map) => Some(parcel_sourcemap::SourceMap::from_json("/", &map).map_err(|err| {
+        CompilationError::from_string(format!("Invalid source map ({})", err))
+      })?),
+
+      None => None,
+    };
+
+    Ok(())
+  }
+
+  pub fn bytes(&self) -> Result<Vec<u8>, CompilationError> {
+    match utils::is_binary_mime_type(&self.mime_type) {
+      true => utils::decode_binary_source(&self.code),
+      false => Ok(self.code.as_bytes().to_vec()),
+    }
   }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct OnBatchModuleResult {
   pub locator: ModuleLocator,
   pub result: Result<OnBatchModule, CompilationError>,
   pub dependencies: Vec<ModuleLocator>,
 }
 
+#[derive(Clone, Copy, Debug)]
+#[napi]
+pub enum BatchProgressStage {
+  Discovered,
+  Transformed,
+  Resolved,
+}
+
+#[derive(Clone, Debug)]
+#[napi(object)]
+pub struct OnBatchProgress {
+  pub stage: BatchProgressStage,
+  pub locator: ModuleLocator,
+  pub discovered: u32,
+  pub transformed: u32,
+  pub resolved: u32,
+}
+
+pub type OnBatchProgressHook = Arc<dyn Fn(OnBatchProgress) + Send + Sync>;
+
+// Modules are sent once their code and path are final, which for JavaScript
+// modules may only happen after the whole graph has been processed (when
+// hashing their filenames or pinning their resolutions)
+pub type OnBatchModuleHook = Arc<dyn Fn(&OnBatchModuleResult) + Send + Sync>;
+
+// Sent once all modules have been reported, with the path each import
+// resolves to (relative to its issuer), keyed by module url then by request
+pub type OnBatchMappingsHook = Arc<dyn Fn(&HashMap<String, HashMap<String, String>>) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct BatchControl {
+  pub cancellation: CancellationToken,
+  pub on_progress: Option<OnBatchProgressHook>,
+  pub on_module: Option<OnBatchModuleHook>,
+  pub on_mappings: Option<OnBatchMappingsHook>,
+}
+
 pub struct OnBatchResult {
   pub results: HashMap<String, OnBatchModuleResult>,
+  pub cancelled: bool,
 }
 
 #[derive(Debug, Default, Clone)]
 #[napi(object)]
 pub struct OnBundleOpts {
   pub batch: OnBatchOpts,
+  pub chunks: HashMap<String, Vec<String>>,
+  pub public_path: Option<String>,
   pub require_on_load: bool,
   pub runtime: Option<ModuleLocator>,
 }
@@ -226,8 +320,33 @@ pub struct OnBundleResultData {
   pub entry: String,
   pub mime_type: String,
 
+  pub filename: String,
+  pub integrity: String,
+
   pub code: String,
   pub map: String,
+
+  pub chunks: Vec<OnBundleChunk>,
+  pub files: Vec<OnBundleFile>,
+}
+
+#[derive(Clone)]
+#[napi(object)]
+pub struct OnBundleChunk {
+  pub name: String,
+  pub filename: String,
+  pub integrity: String,
+
+  pub code: String,
+  pub map: String,
+}
+
+#[derive(Clone)]
+#[napi(object)]
+pub struct OnBundleFile {
+  pub filename: String,
+  pub mime_type: String,
+  pub code: String,
 }
 
 #[derive(Clone)]
@@ -236,19 +355,239 @@ pub struct OnBundleResult {
   pub dependencies: Vec<ModuleLocator>,
 }
 
+#[derive(Clone)]
+#[napi(object)]
+pub struct OnRenderChunkArgs {
+  pub entry: String,
+  pub chunk: Option<String>,
+
+  pub code: String,
+  pub map: String,
+
+  pub meta: HashMap<String, OnBundleModuleMeta>,
+}
+
+#[derive(Clone)]
+#[napi(object)]
+pub struct OnRenderChunkResultData {
+  pub code: String,
+  pub map: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct OnRenderChunkResult {
+  pub result: Result<OnRenderChunkResultData, CompilationError>,
+  pub dependencies: Vec<ModuleLocator>,
+}
+
+#[derive(Clone)]
+#[napi(object)]
+pub struct OnGenerateBundleArgs {
+  pub bundle: OnBundleResultData,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnEmitOpts {
+  pub source_folder: Path,
+  pub dist_folder: Path,
+  pub copy: Vec<String>,
+  pub extensions: HashMap<String, String>,
+  pub rewrite_package_json: bool,
+  pub source_maps: bool,
+  pub variants: Vec<OnEmitVariant>,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnEmitVariant {
+  pub module_format: ModuleFormat,
+  pub extension: String,
+}
+
+pub struct OnEmitArgs {
+  pub batch: OnBatchResult,
+  // The format the batch compiled the JavaScript modules to; their
+  // resolutions are persisted again once their dist paths are known
+  pub module_format: ModuleFormat,
+  pub opts: OnEmitOpts,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnEmitFile {
+  pub locator: ModuleLocator,
+  pub module_format: Option<ModuleFormat>,
+  pub source_path: Path,
+  pub path: Path,
+  pub integrity: String,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnEmitResultData {
+  pub files: Vec<OnEmitFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OnEmitResult {
+  pub result: Result<OnEmitResultData, CompilationError>,
+  pub dependencies: Vec<ModuleLocator>,
+}
+
+#[derive(Debug, Clone)]
+#[napi(object)]
+pub struct OnBuildOpts {
+  pub batch: OnBatchOpts,
+  pub emit: OnEmitOpts,
+}
+
+#[napi(object)]
+pub struct OnBuildArgs {
+  pub locators: Vec<ModuleLocator>,
+  pub opts: OnBuildOpts,
+}
+
 pub type PluginData = Box<dyn std::any::Any + Send + Sync>;
 
-pub struct PluginHook<TArgs, TRes> {
+// Restricts the modules a plugin is called for; the regexp is matched
+// against the module urls (or the requests, for the resolve hooks)
+#[derive(Clone)]
+pub struct PluginFilter {
   pub regexp: Regex,
   pub params: Vec<OptionStringKeyValue>,
-  pub cb: fn (registration_data: Arc<PluginData>, args: TArgs) -> utils::BoxedFuture<Option<TRes>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PluginHookKind {
+  Resolve,
+  Fetch,
+  Transform,
+  RenderChunk,
+  GenerateBundle,
+}
+
+pub trait HookResult {
+  type Value;
+
+  fn new(result: Result<Self::Value, CompilationError>, dependencies: Vec<ModuleLocator>) -> Self;
+
+  fn from_error(err: CompilationError) -> Self where Self: Sized {
+    Self::new(Err(err), vec![])
+  }
+}
+
+macro_rules! impl_hook_result {
+  ($($name:ident => $value:ident),*) => {
+    $(impl HookResult for $name {
+      type Value = $value;
+
+      fn new(result: Result<$value, CompilationError>, dependencies: Vec<ModuleLocator>) -> Self {
+        Self {
+          result,
+          dependencies,
+        }
+      }
+    })*
+  };
+}
+
+impl_hook_result!(
+  OnResolveResult => OnResolveResultData,
+  OnFetchResult => OnFetchResultData,
+  OnTransformResult => OnTransformResultData,
+  OnRenderChunkResult => OnRenderChunkResultData,
+  OnBundleResult => OnBundleResultData
+);
+
+// The durations are self times; the time a hook spends waiting on the
+// following hooks (through `next`) is attributed to them, not to the hook
+#[derive(Clone, Debug, Default)]
+#[napi(object)]
+pub struct HookStats {
+  pub name: String,
+  pub calls: u32,
+  pub timeouts: u32,
+  pub total_ms: f64,
+  pub max_ms: f64,
+}
+
+impl PluginFilter {
+  pub fn matches(&self, str: &str) -> bool {
+    if !self.regexp.is_match(str).unwrap() {
+      return false;
+    }
+
+    if self.params.is_empty() {
+      return true;
+    }
+
+    // Params without value only require the param to be present
+    let query = str.split_once('?').map_or("", |(_, query)| query);
+    let params = utils::parse_query(query);
+
+    self.params.iter().all(|expected| {
+      params.iter().any(|param| {
+        param.name == expected.name && expected.value.as_ref().map_or(true, |value| &param.value == value)
+      })
+    })
+  }
+}
+
+pub type HookNext<TArgs, TRes> = Arc<dyn Fn(TArgs) -> utils::BoxedFuture<TRes> + Send + Sync>;
+
+pub struct HookCall<TArgs, TRes> {
+  pub args: TArgs,
+  pub next: HookNext<TArgs, TRes>,
+}
+
+impl<TArgs: Clone, TRes> Clone for HookCall<TArgs, TRes> {
+  fn clone(&self) -> Self {
+    Self {
+      args: self.args.clone(),
+      next: self.next.clone(),
+    }
+  }
+}
+
+pub type OnQueryTransformHook = fn (data: Arc<PluginData>, args: OnFetchResultData)
+  -> utils::BoxedFuture<OnFetchResult>;
+pub type OnQueryCompileHook = fn (data: Arc<PluginData>, project: &Project, fetch_data: OnFetchResultData, args: OnTransformArgs)
+  -> OnTransformResult;
+
+pub enum QueryTransformHook {
+  // Rewrites the fetched source before it goes through the regular
+  // transforms; this is what the napi callbacks use
+  Fetch(OnQueryTransformHook),
+
+  // Compiles the fetched source in place of the regular transform for
+  // its mime type; unlike the fetch hooks, they also run without hooks
+  Transform(OnQueryCompileHook),
+}
+
+pub struct QueryTransform {
+  pub cb: QueryTransformHook,
   pub data: Arc<PluginData>,
+
+  // Restricts the transform to the modules of the given mime type; the
+  // others go through the regular transforms
+  pub mime_type: Option<String>,
+
+  // Transforms that don't need the source skip the fetch entirely, so
+  // the files don't even have to exist
+  pub needs_source: bool,
+
+  // Also selects the transform through a bare flag (`?raw`) rather than
+  // only through `?transform=raw`; the names of the opted-in transforms
+  // can't be used as flags for other purposes
+  pub bare_flag: bool,
 }
 
-pub type OnResolveHook = fn (data: Arc<PluginData>, args: OnResolveArgs)
-  -> utils::BoxedFuture<Option<OnResolveResult>>;
-pub type OnFetchHook = fn (data: Arc<PluginData>, args: OnFetchArgs)
-  -> utils::BoxedFuture<Option<OnFetchResult>>;
+impl QueryTransform {
+  pub fn applies_to(&self, mime_type: &str) -> bool {
+    self.mime_type.as_ref().map_or(true, |expected| expected == mime_type)
+  }
+}
 
 #[derive(Clone, Debug, Default, Serialize)]
 #[napi(object)]
@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+#[derive(Default)]
+struct CancellationState {
+  cancelled: AtomicBool,
+  notify: Notify,
+}
+
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  state: Arc<CancellationState>,
+}
+
+impl CancellationToken {
+  pub fn cancel(&self) {
+    self.state.cancelled.store(true, Ordering::SeqCst);
+    self.state.notify.notify_waiters();
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.state.cancelled.load(Ordering::SeqCst)
+  }
+
+  pub async fn cancelled(&self) {
+    loop {
+      // The future must be created before checking the flag, otherwise we
+      // could miss a notification sent in-between
+      let notified = self.state.notify.notified();
+
+      if self.is_cancelled() {
+        return;
+      }
+
+      notified.await;
+    }
+  }
+}
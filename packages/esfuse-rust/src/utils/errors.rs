@@ -101,6 +101,39 @@ impl Diagnostic {
       highlights,
     }
   }
+
+  pub fn from_toml(err: &toml::de::Error, text: &str, source: String) -> Self {
+    let highlights = err.span().map(|range| {
+      Highlight {
+        source: Some(source),
+        subject: None,
+        label: None,
+        span: Some(Span {
+          start: position_from_offset(text, range.start),
+          end: position_from_offset(text, range.end),
+        }),
+      }
+    }).map_or(vec![], |h| {
+      vec![h]
+    });
+
+    Self {
+      message: err.message().to_string(),
+      highlights,
+    }
+  }
+}
+
+fn position_from_offset(text: &str, offset: usize) -> Position {
+  let prefix = text.get(0..offset).unwrap_or(text);
+
+  let row = prefix.matches('\n').count() + 1;
+  let col = prefix.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+
+  Position {
+    row: row as u32,
+    col: col as u32,
+  }
 }
 
 #[derive(Clone, Debug, Default, Error, Serialize)]
@@ -194,4 +227,12 @@ impl CompilationError {
       ].to_vec(),
     }
   }
+
+  pub fn from_toml(err: &toml::de::Error, text: &str, subject: String) -> Self {
+    Self {
+      diagnostics: [
+        Diagnostic::from_toml(err, text, subject),
+      ].to_vec(),
+    }
+  }
 }
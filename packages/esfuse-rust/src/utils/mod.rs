@@ -1,14 +1,20 @@
 use arca::Path;
+use base64::Engine;
 pub use fancy_regex::Regex;
 use sha1::{Digest, Sha1};
+use sha2::Sha384;
 use std::{pin::Pin, future::Future, collections::HashMap, sync::{Arc, Mutex}};
+use itertools::Itertools;
 use lazy_static::lazy_static;
+use path_slash::PathBufExt;
 use serde::Serialize;
 use serde_json::{Serializer, json};
 
 use crate::{types::*, CompilationError, Project};
 
+pub mod cancellation;
 pub mod errors;
+pub mod package_json;
 pub mod swc;
 
 pub type BoxedFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
@@ -58,6 +64,136 @@ pub fn get_locator_virtual_path(project: &Project, locator: &ModuleLocator, opts
   Some(module_path)
 }
 
+pub fn get_content_hash<T: AsRef<[u8]>>(content: T) -> String {
+  let mut hasher = Sha1::new();
+  hasher.update(content.as_ref());
+
+  let mut hash = hex::encode(hasher.finalize());
+  hash.truncate(16);
+
+  hash
+}
+
+pub fn get_integrity<T: AsRef<[u8]>>(content: T) -> String {
+  let mut hasher = Sha384::new();
+  hasher.update(content.as_ref());
+
+  format!("sha384-{}", base64::engine::general_purpose::STANDARD.encode(hasher.finalize()))
+}
+
+pub fn get_hashed_path(p: &Path, hash: &str) -> Path {
+  let basename = p.as_str().rsplit('/').next().unwrap_or_default();
+
+  let ext = get_extension(basename);
+  let stem = &basename[0..basename.len() - ext.len()];
+
+  p.dirname().join_str(format!("{}.{}{}", stem, hash, ext))
+}
+
+// Declaration files must match the module flavor of the file they sit
+// next to, otherwise TypeScript wouldn't pick them up
+pub fn get_declaration_extension<P: AsRef<str>>(p: P) -> &'static str {
+  match get_extension(p).as_str() {
+    ".mts" | ".mjs" => ".d.mts",
+    ".cts" | ".cjs" => ".d.cts",
+    _ => ".d.ts",
+  }
+}
+
+pub fn replace_extension(p: &Path, ext: &str) -> Path {
+  let basename = p.as_str().rsplit('/').next().unwrap_or_default();
+
+  let current_ext = get_extension(basename);
+  let stem = &basename[0..basename.len() - current_ext.len()];
+
+  p.dirname().join_str(format!("{}{}", stem, ext))
+}
+
+pub fn get_relative_specifier(from: &Path, to: &Path) -> String {
+  let relative_path = pathdiff::diff_paths(to.to_path_buf(), from.dirname().to_path_buf())
+    .unwrap_or_else(|| to.to_path_buf());
+
+  let relative_str
+    = relative_path.to_slash_lossy().into_owned();
+
+  match relative_str.starts_with("../") {
+    true => relative_str,
+    false => format!("./{}", relative_str),
+  }
+}
+
+pub fn decode_binary_source(source: &str) -> Result<Vec<u8>, CompilationError> {
+  lazy_static! {
+    static ref ENGINE: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+      &base64::alphabet::STANDARD,
+      base64::engine::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+    );
+  }
+
+  ENGINE.decode(source)
+    .map_err(CompilationError::from_err)
+}
+
+pub fn get_package_name_from_path(p: &str) -> Option<&str> {
+  let (_, subpath) = p.rsplit_once("/node_modules/")?;
+
+  let name_len = match subpath.starts_with('@') {
+    true => subpath.match_indices('/').nth(1).map(|(i, _)| i),
+    false => subpath.find('/'),
+  }?;
+
+  Some(&subpath[0..name_len])
+}
+
+pub fn is_package_name(str: &str) -> bool {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^(@[a-z0-9-~][a-z0-9-._~]*/)?[a-z0-9-~][a-z0-9-._~]*$").unwrap();
+  }
+
+  RE.is_match(str).unwrap()
+}
+
+// Bare names are package patterns unless they look like files; packages
+// whose names look like files (`highlight.js`) need the `npm:` prefix
+fn get_chunk_package_pattern(pattern: &str) -> Option<&str> {
+  if let Some(name) = pattern.strip_prefix("npm:") {
+    return Some(name);
+  }
+
+  let is_glob
+    = pattern.contains(['*', '?', '[', '{']);
+  let is_file
+    = mime_guess::from_path(pattern).first().is_some();
+
+  match !is_glob && !is_file && is_package_name(pattern) {
+    true => Some(pattern),
+    false => None,
+  }
+}
+
+pub fn get_manual_chunk<'a>(project: &Project, locator: &ModuleLocator, chunks: &'a HashMap<String, Vec<String>>) -> Option<&'a str> {
+  let physical_path = locator.physical_path(project)?;
+  let relative_path = physical_path.relative_to(&project.root);
+
+  let package_name
+    = get_package_name_from_path(physical_path.as_str());
+
+  let is_match = |pattern: &String| {
+    match get_chunk_package_pattern(pattern) {
+      Some(name) => package_name == Some(name),
+      None => glob_match::glob_match(pattern.trim_start_matches("./"), relative_path.as_str()),
+    }
+  };
+
+  // Sorting the chunk names ensures that a module matched by multiple
+  // groups always end up in the same chunk, whatever the hashmap order
+  chunks.iter()
+    .sorted_by(|a, b| a.0.cmp(b.0))
+    .find(|(_, patterns)| patterns.iter().any(|pattern| is_match(pattern)))
+    .map(|(name, _)| name.as_str())
+}
+
 pub fn get_ext_from_mime(ext: &str) -> &str {
   match ext {
     "text/javascript" => {
@@ -76,6 +212,14 @@ pub fn get_ext_from_mime(ext: &str) -> &str {
       ".json"
     }
 
+    "text/yaml" => {
+      ".yaml"
+    }
+
+    "text/toml" => {
+      ".toml"
+    }
+
     "application/wasm" => {
       ".wasm"
     }
@@ -116,6 +260,14 @@ pub fn get_mime_from_ext(ext: &str) -> &str {
       "application/json"
     }
 
+    ".yaml" | ".yml" => {
+      "text/yaml"
+    }
+
+    ".toml" => {
+      "text/toml"
+    }
+
     ".wasm" => {
       "application/wasm"
     }
@@ -0,0 +1,261 @@
+use arca::Path;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::types::*;
+
+#[derive(Clone, Copy)]
+enum Condition {
+  Default,
+  Import,
+  Require,
+  Types,
+}
+
+impl Condition {
+  fn from_key(key: &str, parent: Self) -> Self {
+    match key {
+      "types" => Self::Types,
+      "import" | "module" => Self::Import,
+      "require" => Self::Require,
+      _ => parent,
+    }
+  }
+
+  fn accepts(&self, module_format: Option<ModuleFormat>) -> bool {
+    match (self, module_format) {
+      (_, None) => true,
+      (Self::Import, Some(module_format)) => module_format == ModuleFormat::Esm,
+      (Self::Require, Some(module_format)) => module_format == ModuleFormat::CommonJs,
+      (Self::Default, Some(_)) | (Self::Types, Some(_)) => true,
+    }
+  }
+}
+
+pub struct PackageJsonRewriter {
+  remappings: BTreeMap<String, Vec<(Option<ModuleFormat>, String)>>,
+}
+
+fn to_declaration_path(p: &str) -> String {
+  if p.ends_with(".d.ts") || p.ends_with(".d.mts") || p.ends_with(".d.cts") {
+    return p.to_string();
+  }
+
+  let ext = super::get_extension(p);
+  format!("{}{}", &p[0..p.len() - ext.len()], super::get_declaration_extension(p))
+}
+
+fn normalize_exports_root(exports: &Value) -> Value {
+  let is_subpath_map = exports.as_object().map_or(false, |entries| {
+    entries.keys().all(|key| key.starts_with('.'))
+  });
+
+  match is_subpath_map {
+    true => exports.clone(),
+    false => Value::Object(Map::from_iter([(String::from("."), exports.clone())])),
+  }
+}
+
+impl PackageJsonRewriter {
+  pub fn new(source_folder: &Path, dist_folder: &Path, files: &[OnEmitFile]) -> Self {
+    let mut remappings: BTreeMap<String, Vec<(Option<ModuleFormat>, String)>> = BTreeMap::new();
+
+    for file in files {
+      remappings.entry(file.source_path.relative_to(source_folder).to_string()).or_default().push((
+        file.module_format,
+        file.path.relative_to(dist_folder).to_string(),
+      ));
+    }
+
+    Self {
+      remappings,
+    }
+  }
+
+  pub fn rewrite(&self, pkg: &Value) -> Value {
+    let mut pkg = pkg.clone();
+
+    let fields = match pkg.as_object_mut() {
+      Some(fields) => fields,
+      None => return pkg,
+    };
+
+    // Adding an exports field to a package that doesn't have one would
+    // prevent its consumers from requiring anything but the package.json
+    let has_exports
+      = fields.contains_key("exports");
+
+    let mut exports = match fields.get("exports").map(normalize_exports_root).and_then(|exports| self.rewrite_exports(&exports, Condition::Default)) {
+      Some(Value::Object(exports)) => exports,
+      _ => Map::new(),
+    };
+
+    exports.insert(String::from("./package.json"), Value::String(String::from("./package.json")));
+
+    let single_fields = [
+      ("main", Condition::Require),
+      ("module", Condition::Import),
+      ("types", Condition::Types),
+      ("typings", Condition::Types),
+    ];
+
+    for (field, condition) in single_fields {
+      let remapped = fields.get(field)
+        .and_then(Value::as_str)
+        .and_then(|target| self.remap_target(target, condition));
+
+      match remapped {
+        Some(target) => fields.insert(field.to_string(), target),
+        None => fields.remove(field),
+      };
+    }
+
+    if !fields.contains_key("main") {
+      let main = match exports.get(".") {
+        Some(Value::String(main)) => Some(main),
+        Some(Value::Object(conditions)) => conditions.get("require").or_else(|| conditions.get("default")).and_then(Value::as_str),
+        _ => None,
+      };
+
+      if let Some(main) = main {
+        fields.insert(String::from("main"), Value::String(main.clone()));
+      }
+    }
+
+    let bin = match fields.get("bin") {
+      Some(Value::String(target)) => self.remap_target(target, Condition::Require),
+      Some(Value::Object(entries)) => {
+        let remapped: Map<String, Value> = entries.iter().filter_map(|(name, target)| {
+          target.as_str()
+            .and_then(|target| self.remap_target(target, Condition::Require))
+            .map(|target| (name.clone(), target))
+        }).collect();
+
+        (!remapped.is_empty()).then_some(Value::Object(remapped))
+      },
+      _ => None,
+    };
+
+    match bin {
+      Some(bin) => fields.insert(String::from("bin"), bin),
+      None => fields.remove("bin"),
+    };
+
+    if has_exports {
+      fields.insert(String::from("exports"), Value::Object(exports));
+    }
+
+    pkg
+  }
+
+  fn rewrite_exports(&self, node: &Value, condition: Condition) -> Option<Value> {
+    match node {
+      Value::String(target) => {
+        self.remap_target(target, condition)
+      },
+
+      Value::Array(fallbacks) => {
+        let remapped: Vec<Value> = fallbacks.iter()
+          .filter_map(|fallback| self.rewrite_exports(fallback, condition))
+          .collect();
+
+        (!remapped.is_empty()).then_some(Value::Array(remapped))
+      },
+
+      Value::Object(entries) => {
+        let remapped: Map<String, Value> = entries.iter().filter_map(|(key, value)| {
+          self.rewrite_exports(value, Condition::from_key(key, condition))
+            .map(|value| (key.clone(), value))
+        }).collect();
+
+        (!remapped.is_empty()).then_some(Value::Object(remapped))
+      },
+
+      // Null targets are used to exclude subpaths from patterns, so we
+      // must preserve them
+      Value::Null => Some(Value::Null),
+
+      _ => None,
+    }
+  }
+
+  fn remap_target(&self, target: &str, condition: Condition) -> Option<Value> {
+    let relative_target = target.trim_start_matches("./");
+    if relative_target.starts_with("../") || relative_target.starts_with('/') {
+      return None;
+    }
+
+    let lookup = match condition {
+      Condition::Types => to_declaration_path(relative_target),
+      _ => relative_target.to_string(),
+    };
+
+    // When a file has been emitted in multiple formats and the condition
+    // doesn't tell us which one to pick, we generate a conditional export
+    if let Condition::Default = condition {
+      let esm_target = self.remap_lookup(&lookup, Condition::Import);
+      let cjs_target = self.remap_lookup(&lookup, Condition::Require);
+
+      if let (Some(esm_target), Some(cjs_target)) = (&esm_target, &cjs_target) {
+        if esm_target != cjs_target {
+          return Some(Value::Object(Map::from_iter([
+            (String::from("import"), Value::String(esm_target.clone())),
+            (String::from("require"), Value::String(cjs_target.clone())),
+          ])));
+        }
+      }
+    }
+
+    self.remap_lookup(&lookup, condition)
+      .map(Value::String)
+  }
+
+  fn remap_lookup(&self, lookup: &str, condition: Condition) -> Option<String> {
+    match lookup.split_once('*') {
+      Some((prefix, suffix)) => {
+        self.remap_pattern(prefix, suffix, condition)
+      },
+
+      None => {
+        self.remappings.get(lookup)?.iter()
+          .find(|(module_format, _)| condition.accepts(*module_format))
+          .map(|(_, dist)| format!("./{}", dist))
+      },
+    }
+  }
+
+  fn remap_pattern(&self, prefix: &str, suffix: &str, condition: Condition) -> Option<String> {
+    let mut dist_suffix = None;
+
+    for (source, dists) in &self.remappings {
+      if source.len() < prefix.len() + suffix.len() || !source.starts_with(prefix) || !source.ends_with(suffix) {
+        continue;
+      }
+
+      let dist = match dists.iter().find(|(module_format, _)| condition.accepts(*module_format)) {
+        Some((_, dist)) => dist,
+        None => continue,
+      };
+
+      let capture
+        = &source[prefix.len()..source.len() - suffix.len()];
+
+      // We only support patterns where the emitted files keep the same path,
+      // save for their suffix (which is typically the file extension)
+      let candidate_suffix = match dist.strip_prefix(prefix).and_then(|rest| rest.strip_prefix(capture)) {
+        Some(candidate_suffix) => candidate_suffix,
+        None => continue,
+      };
+
+      if dist_suffix.map_or(false, |dist_suffix| dist_suffix != candidate_suffix) {
+        return None;
+      }
+
+      dist_suffix = Some(candidate_suffix);
+    }
+
+    dist_suffix.map(|dist_suffix| {
+      format!("./{}*{}", prefix, dist_suffix)
+    })
+  }
+}
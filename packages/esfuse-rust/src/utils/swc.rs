@@ -4,7 +4,7 @@ use swc::{config::{SourceMapsConfig, ModuleConfig}, TransformOutput};
 use swc_common::{GLOBALS, errors::Handler, FileName, comments::SingleThreadedComments};
 use swc_core::ecma::{visit::{as_folder, VisitMut, VisitMutWith}, transforms::base::pass::noop, ast::{EsVersion, self}, utils::quote_str};
 
-use crate::{utils, CompilationError, types::ModuleLocator};
+use crate::{utils, CompilationError, types::{ModuleFormat, ModuleLocator}};
 
 #[derive(Debug, Clone, Default)]
 pub struct ErrorBuffer(pub std::sync::Arc<std::sync::Mutex<Vec<swc_common::errors::Diagnostic>>>);
@@ -61,6 +61,12 @@ impl<'a> DependencyUpdater<'a> {
   pub fn new(mappings: &'a HashMap<String, String>) -> Self {
     Self {mappings}
   }
+
+  fn update_source(&self, src: &mut ast::Str) {
+    if let Some(mapping) = self.mappings.get(&src.value.to_string()) {
+      *src = quote_str!(mapping.as_str());
+    }
+  }
 }
 
 impl<'a> VisitMut for DependencyUpdater<'a> {
@@ -72,10 +78,44 @@ impl<'a> VisitMut for DependencyUpdater<'a> {
         *expr = quote_str!(mapping.as_str()).into();
       }
     }
+
+    if e.callee.is_import() && !e.args.is_empty() {
+      if let Some((specifier, _)) = require_param_to_specifier(&e.args[0].expr) {
+        if let Some(mapping) = self.mappings.get(&specifier) {
+          e.args[0].expr = quote_str!(mapping.as_str()).into();
+        }
+      }
+    }
+  }
+
+  fn visit_mut_import_decl(&mut self, n: &mut ast::ImportDecl) {
+    self.update_source(&mut n.src);
+  }
+
+  fn visit_mut_export_all(&mut self, n: &mut ast::ExportAll) {
+    self.update_source(&mut n.src);
+  }
+
+  fn visit_mut_named_export(&mut self, n: &mut ast::NamedExport) {
+    if let Some(src) = &mut n.src {
+      self.update_source(src);
+    }
+  }
+}
+
+pub fn get_module_config(module_format: ModuleFormat) -> Option<ModuleConfig> {
+  match module_format {
+    ModuleFormat::CommonJs => Some(ModuleConfig::CommonJs(serde_json::from_str(r#"{
+      "ignoreDynamic": true
+    }"#).unwrap())),
+
+    // Leaving the module config empty preserves the import and
+    // export statements as they are
+    ModuleFormat::Esm => None,
   }
 }
 
-pub fn persist_resolutions(locator: &ModuleLocator, code: &str, map: &str, resolutions: &HashMap<String, String>) -> Result<TransformOutput, CompilationError> {
+pub fn persist_resolutions(locator: &ModuleLocator, code: &str, map: Option<&str>, resolutions: &HashMap<String, String>, module_format: ModuleFormat) -> Result<TransformOutput, CompilationError> {
   let cm = Arc::<swc_common::SourceMap>::default();
   let c = swc::Compiler::new(cm.clone());
 
@@ -93,16 +133,14 @@ pub fn persist_resolutions(locator: &ModuleLocator, code: &str, map: &str, resol
   swc_config.config.jsc.target = Some(EsVersion::Es2022);
 
   swc_config.source_maps = Some(SourceMapsConfig::Bool(true));
-  swc_config.config.input_source_map = Some(swc::config::InputSourceMap::Str(map.to_string()));
+  swc_config.config.input_source_map = map.map(|map| swc::config::InputSourceMap::Str(map.to_string()));
 
   swc_config.config.jsc.minify = Some(serde_json::from_str(r#"{
     "compress": false,
     "mangle": false
   }"#).unwrap());
 
-  swc_config.config.module = Some(ModuleConfig::CommonJs(serde_json::from_str(r#"{
-    "ignoreDynamic": true
-  }"#).unwrap()));
+  swc_config.config.module = get_module_config(module_format);
 
   let mut dependency_updater
     = DependencyUpdater::new(resolutions);
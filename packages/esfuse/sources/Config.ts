@@ -6,7 +6,24 @@ export type PatternConfig = {
 };
 
 export type BuildConfig = {
+  concurrency?: number;
   include?: Array<string>;
+  copy?: Array<string>;
+  hashFilenames?: boolean;
+  dual?: boolean;
+  declarations?: boolean;
+};
+
+export type SwcPluginConfig = {
+  path: string;
+  options?: any;
+  include?: Array<string>;
+};
+
+export type SwcBuiltinsConfig = {
+  emotion?: boolean;
+  graphqlTag?: boolean;
+  styledComponents?: boolean;
 };
 
 export type ServerConfig = {
@@ -14,9 +31,16 @@ export type ServerConfig = {
 };
 
 export type Config = {
+  // Each chunk lists package names (`react`, or `npm:highlight.js` for names
+  // that look like files) and globs relative to the project root
+  chunks?: Record<string, Array<string>>;
   patterns?: PatternConfig;
   builds?: Record<string, BuildConfig>;
   servers?: Record<string, ServerConfig>;
+  swcBuiltins?: SwcBuiltinsConfig;
+  swcPlugins?: Array<SwcPluginConfig>;
+  // Milliseconds after which the hooks without their own timeout fail
+  hookTimeout?: number;
 };
 
 export type ConfigFn =
@@ -18,21 +18,24 @@ import * as miscUtils                                from 'esfuse/sources/utils/
 import * as nodeUtils                                from 'esfuse/sources/utils/nodeUtils';
 
 import {
+  BatchModuleResult,
   FetchResult,
+  ModuleFormat,
   ModuleLocator,
   OnBatchOpts,
+  OnBatchProgress,
   OnBundleOpts,
   OnFetchArgs,
   OnResolveArgs,
   OnTransformOpts,
+  ProjectDefinition,
   ProjectHandle,
   ResolutionKind,
   ResolveResult,
 } from '@esfuse/compiler';
 
-type ExportsFieldValue =
-  | {[k: string]: ExportsFieldValue}
-  | string;
+export type ResolveHook = (args: OnResolveArgs, next: (args: OnResolveArgs) => Promise<ResolveResult>) => Promise<ResolveResult | undefined>;
+export type FetchHook = (args: OnFetchArgs, next: (args: OnFetchArgs) => Promise<FetchResult>) => Promise<FetchResult | undefined>;
 
 export type WatchEvent = {
   changes: Map<string | ModuleLocator, `added` | `removed` | `changed`>;
@@ -55,14 +58,50 @@ export function extractResult<T extends {value?: any, error?: any}>(res: T) {
 
 export class Project {
   handle: ProjectHandle;
-  config = defaultConfig();
+  config: Config;
   tailwind: Tailwind;
 
   constructor(public root: string) {
-    this.handle = ProjectHandle.create({
+    this.config = this.loadConfig();
+
+    this.handle = ProjectHandle.create(this.getProjectDefinition());
+    this.tailwind = new Tailwind(this);
+  }
+
+  getConfigPath() {
+    return path.join(this.root, `esfuse.config.ts`);
+  }
+
+  loadConfig() {
+    const config = defaultConfig();
+
+    const configPath = this.getConfigPath();
+    if (fs.existsSync(configPath)) {
+      // Otherwise reloads would get the module evaluated at startup
+      delete require.cache[require.resolve(configPath)];
+
+      const {config: configFn} = require(configPath);
+
+      mergeWith(config, configFn(), (left: any, right: any) => {
+        return Array.isArray(left) ? right : undefined;
+      }) as Config;
+    }
+
+    return config;
+  }
+
+  // The operations already running keep using the previous hooks & options,
+  // only the ones started afterwards see the new configuration
+  reloadConfig() {
+    this.config = this.loadConfig();
+    this.handle.reconfigure(this.getProjectDefinition());
+  }
+
+  getProjectDefinition(): ProjectDefinition {
+    return {
       root: this.root,
       namespaces: {
-        [`ylc`]: path.join(root, `.yarn/cache`),
+        [`ylc`]: path.join(this.root, `.yarn/cache`),
         [`ygc`]: path.join(os.homedir(), `.yarn/berry/cache`),
       },
       onResolve: [{
@@ -87,18 +126,33 @@ export class Project {
           return await this.onDynamicFetch(args);
         }),
       }],
-    });
+      onTransform: [],
+      onRenderChunk: [],
+      onGenerateBundle: [],
+      transforms: {},
+      hookTimeout: this.config.hookTimeout,
+    };
+  }
 
-    const configPath = path.join(this.root, `esfuse.config.ts`);
-    if (fs.existsSync(configPath)) {
-      const {config} = require(configPath);
+  // The hooks receive the continuation id from the native side; calling
+  // next runs the following hooks, then the default implementation. It
+  // stops working once the hook settles, throws, or times out.
+  resolveHook(cb: ResolveHook) {
+    return async (args: OnResolveArgs, nextId: number) => {
+      return await cb(args, nextArgs => this.handle.resolveNext(nextId, nextArgs));
+    };
+  }
 
-      mergeWith(this.config, config(), (left: any, right: any) => {
-        return Array.isArray(left) ? right : undefined;
-      }) as Config;
-    }
+  fetchHook(cb: FetchHook) {
+    return async (args: OnFetchArgs, nextId: number) => {
+      return await cb(args, nextArgs => this.handle.fetchNext(nextId, nextArgs));
+    };
+  }
 
-    this.tailwind = new Tailwind(this);
+  // Self times, sorted by total time; the time a hook spends waiting on the
+  // following hooks isn't counted
+  getHookStats() {
+    return this.handle.getHookStats();
   }
 
   dispose() {
@@ -127,6 +181,19 @@ export class Project {
     this.watchEvents.rename.clear();
     this.watchEvents.change.clear();
 
+    const configPath = this.getConfigPath();
+    const configChanged = [...changes.keys()].some(p => {
+      return typeof p === `string` && path.resolve(this.root, p) === configPath;
+    });
+
+    if (configChanged) {
+      try {
+        this.reloadConfig();
+      } catch (err) {
+        console.log(err);
+      }
+    }
+
     const e: WatchEvent = {changes};
     for (const listener of this.watchListeners) {
       listener(e);
@@ -222,6 +289,22 @@ export class Project {
     }));
   }
 
+  getSwcBuiltins() {
+    return {
+      emotion: this.config.swcBuiltins?.emotion ?? false,
+      graphqlTag: this.config.swcBuiltins?.graphqlTag ?? false,
+      styledComponents: this.config.swcBuiltins?.styledComponents ?? false,
+    };
+  }
+
+  getSwcPlugins() {
+    return (this.config.swcPlugins ?? []).map(plugin => ({
+      path: plugin.path,
+      options: plugin.options ?? {},
+      include: plugin.include ?? [],
+    }));
+  }
+
   async transform(locator: ModuleLocator, opts?: OnTransformOpts) {
     return extractResult(await this.handle.transform({
       locator,
@@ -230,7 +313,11 @@ export class Project {
         userData: {},
         ...opts,
         swc: {
+          emitDeclarations: false,
+          moduleFormat: ModuleFormat.CommonJs,
           promisifyBody: false,
+          plugins: this.getSwcPlugins(),
+          builtins: this.getSwcBuiltins(),
           useEsfuseRuntime: false,
           ...opts?.swc,
         },
@@ -246,7 +333,7 @@ export class Project {
     return this.transform(locator, opts);
   }
 
-  async build(buildName: string) {
+  async build(buildName: string, {signal, onProgress, onModule}: {signal?: AbortSignal, onProgress?: (progress: OnBatchProgress) => void, onModule?: (module: BatchModuleResult) => void} = {}) {
     if (typeof this.config.builds === `undefined` || !Object.prototype.hasOwnProperty.call(this.config.builds, buildName))
       throw new UsageError(`No build configuration for "${buildName}"`);
 
@@ -268,100 +355,80 @@ export class Project {
     const absoluteDistFolder = path.join(this.root, distFolder);
     const absoluteSourceFolder = path.join(this.root, sourceFolder);
 
-    let pkgJson;
-    try {
-      pkgJson = await fs.promises.readFile(path.join(absoluteSourceFolder, `package.json`), `utf8`);
-    } catch (err: any) {
-      if (err.code === `ENOENT`) {
-        throw new UsageError(`No package.json found - is the sourceFolder option well-configured?`);
-      } else {
-        throw err;
-      }
-    }
+    if (!fs.existsSync(path.join(absoluteSourceFolder, `package.json`)))
+      throw new UsageError(`No package.json found - is the sourceFolder option well-configured?`);
 
     const files = await this.glob(buildConfig.include ?? [], {absolute: true, cwd: absoluteSourceFolder});
     if (files.length === 0)
       throw new UsageError(`Empty build - is the sourceFolder option well-configured?`);
 
-    const generation = await this.handle.batch({
+    const generation = extractResult(await this.withCancellation(signal, cancellation => this.handle.build({
       locators: files.map(file => {
         return this.locatorFromPath(file)!;
       }),
       opts: {
-        generatedModuleFolder: path.join(absoluteSourceFolder, `generated`),
-        pinResolutions: true,
+        batch: {
+          concurrency: buildConfig.concurrency,
+          emitDeclarations: buildConfig.declarations ?? false,
+          generatedModuleFolder: path.join(absoluteSourceFolder, `generated`),
+          hashFilenames: buildConfig.hashFilenames ?? false,
+          moduleFormat: ModuleFormat.CommonJs,
+          pinResolutions: true,
+          promisifyEntryPoint: false,
+          swcBuiltins: this.getSwcBuiltins(),
+          swcPlugins: this.getSwcPlugins(),
+          useEsfuseRuntime: false,
+          userData: {},
+          traverseDependencies: true,
+          traverseNatives: false,
+          traversePackages: false,
+          traverseVendors: false,
+        },
+        emit: {
+          sourceFolder: absoluteSourceFolder,
+          distFolder: absoluteDistFolder,
+          copy: buildConfig.copy ?? [],
+          extensions: {
+            [`text/javascript`]: `.js`,
+          },
+          rewritePackageJson: true,
+          sourceMaps: false,
+          variants: buildConfig.dual ? [
+            {moduleFormat: ModuleFormat.Esm, extension: `.mjs`},
+            {moduleFormat: ModuleFormat.CommonJs, extension: `.cjs`},
+          ] : [],
+        },
+      },
+    }, cancellation, onProgress, onModule)));
+
+    if (generation.error)
+      throw new Error(`Build failed:\n\n${generation.error.diagnostics.map(diagnostic => `- ${diagnostic.message}`).join(`\n`)}`);
+  }
+
+  // Modules are reported through onModule as soon as their code is final,
+  // without waiting for the whole batch to complete; the batch then only
+  // returns a summary. The resolution mappings are reported last, through
+  // onMappings.
+  async batch(locators: Array<ModuleLocator>, opts: Partial<OnBatchOpts> = {}, {signal, onProgress, onModule, onMappings}: {signal?: AbortSignal, onProgress?: (progress: OnBatchProgress) => void, onModule?: (module: BatchModuleResult) => void, onMappings?: (mappings: Record<string, Record<string, string>>) => void} = {}) {
+    return await this.withCancellation(signal, cancellation => this.handle.batch({
+      locators,
+      opts: {
+        emitDeclarations: false,
+        hashFilenames: false,
+        moduleFormat: ModuleFormat.CommonJs,
+        pinResolutions: false,
         promisifyEntryPoint: false,
+        swcBuiltins: this.getSwcBuiltins(),
+        swcPlugins: this.getSwcPlugins(),
         useEsfuseRuntime: false,
         userData: {},
         traverseDependencies: true,
         traverseNatives: false,
-        traversePackages: false,
         traverseVendors: false,
+        traversePackages: false,
+        ...opts,
       },
-    });
-
-    const remappings = new Map<string, string>();
-
-    miscUtils.rethrowAllSettled(await Promise.allSettled(generation.map(async entryResult => {
-      const entry = extractResult(entryResult);
-
-      if (entry.error)
-        throw new Error(`Build failed for ${entry.locator.url}`);
-
-      if (entry.value.mimeType !== `text/javascript`)
-        throw new Error(`Assertion failed: Only JavaScript files can be generated (got ${entry.value.mimeType} for ${entry.locator.url})`);
-
-      const absoluteSourcePath = entry.value.imaginaryPath!;
-      const relativeSourcePath = path.relative(absoluteSourceFolder, absoluteSourcePath);
-
-      const ext = `.js`;
-      const absoluteDistPath = path.join(absoluteDistFolder, relativeSourcePath.replace(/(?<=[^/])\.[^.]+$/, ext));
-
-      await fs.promises.mkdir(path.dirname(absoluteDistPath), {recursive: true});
-      await fs.promises.writeFile(absoluteDistPath, entry.value.code);
-
-      remappings.set(absoluteSourcePath, absoluteDistPath);
-    })));
-
-    const pkgJsonData = JSON.parse(pkgJson);
-
-    const traverse = (node: ExportsFieldValue) => {
-      if (typeof node !== `string`) {
-        const transformed: Record<string, ExportsFieldValue> = {};
-
-        for (const [key, value] of Object.entries(node)) {
-          const newValue = traverse(value);
-          if (newValue !== null) {
-            transformed[key] = newValue;
-          }
-        }
-
-        if (Object.keys(transformed).length > 0) {
-          return transformed;
-        } else {
-          return null;
-        }
-      } else {
-        const resolved = path.join(this.root, sourceFolder, node);
-        const remapping = remappings.get(resolved);
-
-        if (typeof remapping !== `undefined`) {
-          return `./${path.relative(absoluteDistFolder, remapping)}`;
-        } else {
-          return null;
-        }
-      }
-    };
-
-    const originalExports = pkgJsonData.exports ?? {};
-    pkgJsonData.exports = traverse(originalExports) ?? {};
-    pkgJsonData.exports[`./package.json`] = `./package.json`;
-    pkgJsonData.main = pkgJsonData.exports[`.`];
-
-    await fs.promises.writeFile(
-      path.join(this.root, distFolder, `package.json`),
-      `${JSON.stringify(pkgJsonData, null, 2)}\n`,
-    );
+    }, cancellation, onProgress, onModule, onMappings));
   }
 
   async run(locator: ModuleLocator, opts: {userData?: any, contextify?: (ctx: any) => void} = {}): Promise<unknown> {
@@ -390,10 +457,14 @@ export class Project {
 
     opts.contextify?.(ctx);
 
-    const sourceMappingUrl = `data:application/json;base64,${Buffer.from(res.value!.code).toString(`base64`)}`;
+    // There's no script loader in the vm context, so the chunks are evaluated
+    // before the entry point, which then finds them already loaded
+    for (const chunk of [...res.value!.chunks, res.value!]) {
+      const sourceMappingUrl = `data:application/json;base64,${Buffer.from(chunk.map).toString(`base64`)}`;
 
-    const script = new vm.Script(`${res.value!.code}\n//# sourceMappingURL=${sourceMappingUrl}\n`);
-    script.runInContext(ctx);
+      const script = new vm.Script(`${chunk.code}\n//# sourceMappingURL=${sourceMappingUrl}\n`);
+      script.runInContext(ctx);
+    }
 
     return await ctx.module.exports;
   }
@@ -466,16 +537,42 @@ export class Project {
     };
   }
 
-  async bundle(locator: ModuleLocator, opts: Partial<OnBatchOpts & OnBundleOpts> = {}) {
-    return extractResult(await this.handle.bundle({
+  private async withCancellation<T>(signal: AbortSignal | undefined, fn: (cancellation: number | undefined) => Promise<T>) {
+    if (typeof signal === `undefined`)
+      return await fn(undefined);
+
+    const cancellation = this.handle.createCancellation();
+    const onAbort = () => this.handle.cancel(cancellation);
+
+    if (signal.aborted)
+      onAbort();
+
+    signal.addEventListener(`abort`, onAbort, {once: true});
+
+    try {
+      return await fn(cancellation);
+    } finally {
+      signal.removeEventListener(`abort`, onAbort);
+      this.handle.releaseCancellation(cancellation);
+    }
+  }
+
+  async bundle(locator: ModuleLocator, opts: Partial<OnBatchOpts & OnBundleOpts> = {}, {signal, onProgress}: {signal?: AbortSignal, onProgress?: (progress: OnBatchProgress) => void} = {}) {
+    return extractResult(await this.withCancellation(signal, cancellation => this.handle.bundle({
       locator,
       opts: {
+        chunks: this.config.chunks ?? {},
         requireOnLoad: false,
         runtime: this.locatorFromPath(path.join(__dirname, `runtimes/base.ts`))!,
         ...opts,
         batch: {
+          emitDeclarations: false,
+          hashFilenames: false,
+          moduleFormat: ModuleFormat.CommonJs,
           pinResolutions: false,
           promisifyEntryPoint: false,
+          swcBuiltins: this.getSwcBuiltins(),
+          swcPlugins: this.getSwcPlugins(),
           useEsfuseRuntime: true,
           userData: {},
           traverseDependencies: true,
@@ -485,7 +582,7 @@ export class Project {
           ...opts,
         },
       },
-    }));
+    }, cancellation, onProgress)));
   }
 
   private async onDynamicResolve(args: OnResolveArgs): Promise<ResolveResult> {
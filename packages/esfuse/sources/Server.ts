@@ -35,11 +35,20 @@ export class Server {
 
   endpoints = {
     [`GET:/_dev/bundle`]: this.bundleHandler,
+    [`GET:/_dev/chunk`]: this.chunkHandler,
     [`GET:/_dev/file`]: this.fileHandler,
     [`GET:/_dev/internal/runtime`]: this.runtimeHandler,
     [`GET:/_dev/internal/tailwind`]: this.tailwindHandler,
   };
 
+  // Chunk filenames contain their content hash, so they can be kept around
+  // until the next time the same chunk gets bundled
+  chunks = new Map<string, {code: string, map: string}>();
+
+  // Files emitted by the generate bundle hooks are served beside the chunks,
+  // so they can reference each other through the public path
+  files = new Map<string, {mimeType: string, code: string}>();
+
   http?: HttpServer;
   ws = new WebSocketServer({
     noServer: true,
@@ -202,7 +211,13 @@ export class Server {
 
   async bundleHandler(req: Request): Promise<Response> {
     const locator = this.project.locatorFromUrl(req.url.pathname.replace(/\.map$/, ``).replace(/^\/_dev\/bundle\//, `/_dev/file/`) + req.url.search)!;
-    const res = await this.project.bundle(locator, {requireOnLoad: true, userData: this.getUserData()});
+    const res = await this.project.bundle(locator, {requireOnLoad: true, publicPath: `/_dev/chunk/`, userData: this.getUserData()});
+
+    for (const chunk of res.value?.chunks ?? [])
+      this.chunks.set(chunk.filename, chunk);
+
+    for (const file of res.value?.files ?? [])
+      this.files.set(file.filename, file);
 
     if (res.value && req.url.pathname.endsWith(`.map`))
       Object.assign(res.value, {mimeType: `application/json`, code: res.value.map});
@@ -210,6 +225,23 @@ export class Server {
     return this.renderTransformResult(res);
   }
 
+  async chunkHandler(req: Request, subPath: string): Promise<Response> {
+    const file = this.files.get(subPath.replace(/^\//, ``));
+    if (typeof file !== `undefined`)
+      return this.renderTransformResult({value: file, error: null});
+
+    const chunk = this.chunks.get(subPath.replace(/^\//, ``).replace(/\.map$/, ``));
+    if (typeof chunk === `undefined`)
+      return this.error(404, `Chunk not found (${subPath})`)();
+
+    return this.renderTransformResult({
+      value: req.url.pathname.endsWith(`.map`)
+        ? {mimeType: `application/json`, code: chunk.map}
+        : {mimeType: `text/javascript`, code: chunk.code},
+      error: null,
+    });
+  }
+
   async fileHandler(req: Request): Promise<Response> {
     const locator = this.project.locatorFromUrl(req.url.pathname.replace(/\.map$/, ``) + req.url.search)!;
     const res = await this.project.bundle(locator, {requireOnLoad: true, userData: this.getUserData(), onlyEntryPoint: true});
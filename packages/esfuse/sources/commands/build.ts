@@ -1,4 +1,6 @@
+import {OnBatchProgress} from '@esfuse/compiler';
 import {Command, Option} from 'clipanion';
+import throttle          from 'lodash/throttle';
 
 import {Project}         from 'esfuse/sources/Project';
 import * as miscUtils    from 'esfuse/sources/utils/miscUtils';
@@ -17,8 +19,26 @@ export class BuildCommand extends Command {
       ? Object.keys(app.config.builds ?? {})
       : [this.name];
 
+    // The progress line is redrawn in place, which only makes sense when
+    // the output goes to a terminal
+    const isInteractive = !!(this.context.stderr as NodeJS.WriteStream).isTTY;
+    const progress = new Map<string, OnBatchProgress>();
+
+    const renderProgress = throttle(() => {
+      const line = [...progress].map(([buildName, {discovered, transformed, resolved}]) => {
+        return `${buildName}: ${resolved}/${discovered} modules (${transformed} transformed)`;
+      }).join(`, `);
+
+      this.context.stderr.write(`\r\x1b[K${line}`);
+    }, 100);
+
     const buildPromises = buildNames.map(async buildName => {
-      await app.build(buildName);
+      await app.build(buildName, {
+        onProgress: isInteractive ? data => {
+          progress.set(buildName, data);
+          renderProgress();
+        } : undefined,
+      });
     });
 
     let results;
@@ -26,6 +46,11 @@ export class BuildCommand extends Command {
       results = await Promise.allSettled(buildPromises);
     } finally {
       app.dispose();
+
+      if (isInteractive && progress.size > 0) {
+        renderProgress.cancel();
+        this.context.stderr.write(`\r\x1b[K`);
+      }
     }
 
     miscUtils.rethrowAllSettled(results);
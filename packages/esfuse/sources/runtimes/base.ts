@@ -32,6 +32,13 @@ type EsfuseMetaInfo = Record<string, {
   resolutions: Record<string, string>;
 }>;
 
+type EsfuseChunkInfo = {
+  url: string | null;
+  integrity: string | null;
+  loaded: boolean;
+  promise: Promise<void> | null;
+};
+
 type EsfuseModuleInfo = {
   instances: Set<string> | null;
   factory: EsfuseModuleFactory;
@@ -52,6 +59,11 @@ interface EsfuseRuntime {
   modules: Map<string, EsfuseModuleInfo>;
   refreshRequests: Set<string>;
 
+  chunks: Map<string, EsfuseChunkInfo>;
+  registerChunks(chunks: Record<string, {url: string, integrity: string}>): void;
+  chunkLoaded(name: string): void;
+  loadChunks(): Promise<void>;
+
   currentMeta: EsfuseMetaInfo;
   meta(info: EsfuseMetaInfo): void;
 
@@ -76,6 +88,50 @@ var $esfuse$: EsfuseRuntime;
     modules: new Map<string, EsfuseModuleInfo>(),
     refreshRequests: new Set<string>(),
 
+    chunks: new Map<string, EsfuseChunkInfo>(),
+
+    registerChunks(chunks) {
+      for (const [name, {url, integrity}] of Object.entries(chunks)) {
+        const chunkInfo = upsertChunkInfo(name);
+
+        chunkInfo.url = url;
+        chunkInfo.integrity = integrity;
+      }
+    },
+
+    chunkLoaded(name) {
+      upsertChunkInfo(name).loaded = true;
+    },
+
+    loadChunks() {
+      const promises = [...$esfuse$.chunks].map(([name, chunkInfo]) => {
+        if (chunkInfo.loaded)
+          return undefined;
+
+        // Outside of browsers, the host is expected to evaluate the chunks
+        // before the entry point (see Project#run)
+        if (typeof document === `undefined` || chunkInfo.url === null)
+          return Promise.reject(new Error(`Chunk not loaded: ${name}`));
+
+        return chunkInfo.promise ??= new Promise<void>((resolve, reject) => {
+          const script = document.createElement(`script`);
+
+          if (chunkInfo.integrity !== null) {
+            script.integrity = chunkInfo.integrity;
+            script.crossOrigin = `anonymous`;
+          }
+
+          script.addEventListener(`load`, () => resolve());
+          script.addEventListener(`error`, () => reject(new Error(`Failed to load chunk: ${name}`)));
+
+          script.src = chunkInfo.url!;
+          document.head.appendChild(script);
+        });
+      });
+
+      return Promise.all(promises).then(() => {});
+    },
+
     log(msg, ...extra) {
       console.log(msg, ...extra);
     },
@@ -204,6 +260,17 @@ var $esfuse$: EsfuseRuntime;
     },
   };
 
+  function upsertChunkInfo(name: string) {
+    let chunkInfo = $esfuse$.chunks.get(name);
+
+    if (typeof chunkInfo === `undefined`) {
+      chunkInfo = {url: null, integrity: null, loaded: false, promise: null};
+      $esfuse$.chunks.set(name, chunkInfo);
+    }
+
+    return chunkInfo;
+  }
+
   function upsertModuleInfo(p: string, factory: EsfuseModuleFactory) {
     const moduleInfo = $esfuse$.modules.get(p);
     if (typeof moduleInfo !== `undefined` && !$esfuse$.refreshRequests.has(p))
@@ -1,4 +1,4 @@
-const {ProjectHandle} = require(`@esfuse/compiler`);
+const {ModuleFormat, ProjectHandle} = require(`@esfuse/compiler`);
 const addHook = require(`pirates`).addHook;
 
 const handler = ProjectHandle.create({
@@ -6,6 +6,10 @@ const handler = ProjectHandle.create({
   namespaces: {},
   onResolve: [],
   onFetch: [],
+  onTransform: [],
+  onRenderChunk: [],
+  onGenerateBundle: [],
+  transforms: {},
 });
 
 addHook((code, path) => {
@@ -16,7 +20,15 @@ addHook((code, path) => {
     opts: {
       staticResolutions: {},
       swc: {
+        emitDeclarations: false,
+        moduleFormat: ModuleFormat.CommonJs,
         promisifyBody: false,
+        plugins: [],
+        builtins: {
+          emotion: false,
+          graphqlTag: false,
+          styledComponents: false,
+        },
         useEsfuseRuntime: false,
       },
       userData: {
@@ -0,0 +1,104 @@
+import {BatchModuleResult, OnTransformHookArgs, TransformStage} from '@esfuse/compiler';
+
+import {makeHookedApplication, makeTestApplication}             from './helpers';
+
+const INDEX_URL = `/_dev/file/app/fixtures/build-layout/index.ts`;
+const FORMAT_URL = `/_dev/file/app/fixtures/build-layout/utils/format.ts`;
+
+describe(`Batch`, () => {
+  it(`should only stream the modules once their code is final`, async () => {
+    const app = makeTestApplication();
+
+    try {
+      const opts = {
+        hashFilenames: true,
+        pinResolutions: true,
+      };
+
+      const results = await app.batch([app.locatorFromUrl(INDEX_URL)!], opts);
+      if (!Array.isArray(results))
+        throw new Error(`Expected the batch to return the modules`);
+
+      const streamed = new Map<string, BatchModuleResult>();
+
+      const summary = await app.batch([app.locatorFromUrl(INDEX_URL)!], opts, {
+        onModule: module => {
+          streamed.set(module.locator.url, module);
+        },
+      });
+
+      // The module callbacks and the batch result go through different
+      // queues; we let the pending callbacks run before comparing them
+      await new Promise(resolve => setImmediate(resolve));
+
+      expect(summary).toEqual({modules: results.length, errors: 0});
+      expect(results.map(result => result.locator.url).sort()).toEqual([...streamed.keys()].sort());
+
+      for (const result of results) {
+        const streamedModule = streamed.get(result.locator.url)!.value!;
+
+        expect(streamedModule.code).toEqual(result.value!.code);
+        expect(streamedModule.integrity).toEqual(result.value!.integrity);
+        expect(streamedModule.imaginaryPath).toEqual(result.value!.imaginaryPath);
+      }
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should report the resolution mappings once the batch is done`, async () => {
+    const app = makeTestApplication();
+
+    try {
+      for (const pinResolutions of [false, true]) {
+        const reported: Array<Record<string, Record<string, string>>> = [];
+
+        await app.batch([app.locatorFromUrl(INDEX_URL)!], {pinResolutions}, {
+          onMappings: mappings => {
+            reported.push(mappings);
+          },
+        });
+
+        await new Promise(resolve => setImmediate(resolve));
+
+        expect(reported).toEqual([{
+          [INDEX_URL]: {[`./utils/format`]: expect.any(String)},
+          [FORMAT_URL]: {},
+        }]);
+      }
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should report the modules whose resolutions can't be pinned as failed`, async () => {
+    const app = makeHookedApplication({
+      onTransform: [{
+        regexp: `format\\.ts$`,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.After)
+            return undefined;
+
+          return {value: {...data, code: `export const = ;\n`}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const results = await app.batch([app.locatorFromUrl(INDEX_URL)!], {
+        pinResolutions: true,
+      });
+
+      if (!Array.isArray(results))
+        throw new Error(`Expected the batch to return the modules`);
+
+      const byUrl = new Map(results.map(result => [result.locator.url, result]));
+
+      expect(byUrl.get(INDEX_URL)!.error).toBeUndefined();
+      expect(byUrl.get(FORMAT_URL)!.value).toBeUndefined();
+      expect(byUrl.get(FORMAT_URL)!.error?.diagnostics).not.toHaveLength(0);
+    } finally {
+      app.dispose();
+    }
+  });
+});
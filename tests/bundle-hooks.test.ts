@@ -0,0 +1,133 @@
+import {OnGenerateBundleArgs, OnRenderChunkArgs} from '@esfuse/compiler';
+
+import {Server}                                  from 'esfuse/sources/Server';
+
+import {makeHookedApplication}                   from './helpers';
+
+const ENTRY_URL = `/_dev/file/app/fixtures/chunks/entry.ts`;
+
+describe(`Bundle hooks`, () => {
+  it(`should let the render hooks rewrite each chunk`, async () => {
+    const rendered: Array<string | undefined> = [];
+
+    const app = makeHookedApplication({
+      onRenderChunk: [{
+        regexp: ``,
+        cb: async ({chunk, code}: OnRenderChunkArgs) => {
+          rendered.push(chunk);
+          return {value: {code: `/* banner */\n${code}`}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const res = await app.bundle(app.locatorFromUrl(ENTRY_URL)!, {
+        chunks: {vendor: [`fixtures/chunks/vendor/**`]},
+      });
+
+      expect(rendered).toEqual([`vendor`, undefined]);
+
+      expect(res.value!.code).toMatch(/^\/\* banner \*\/\n/);
+      expect(res.value!.chunks[0].code).toMatch(/^\/\* banner \*\/\n/);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should give the module meta to the render hooks`, async () => {
+    let meta: OnRenderChunkArgs[`meta`] | undefined;
+
+    const app = makeHookedApplication({
+      onRenderChunk: [{
+        regexp: ``,
+        cb: async (args: OnRenderChunkArgs) => {
+          meta = args.meta;
+          return undefined;
+        },
+      }],
+    });
+
+    try {
+      await app.bundle(app.locatorFromUrl(ENTRY_URL)!);
+
+      expect(Object.keys(meta!).sort()).toEqual([
+        `/_dev/file/app/fixtures/chunks/entry.ts`,
+        `/_dev/file/app/fixtures/chunks/local.ts`,
+        `/_dev/file/app/fixtures/chunks/vendor/greet.ts`,
+      ]);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should compose the source maps returned by the render hooks`, async () => {
+    const app = makeHookedApplication({
+      onRenderChunk: [{
+        regexp: ``,
+        cb: async ({code}: OnRenderChunkArgs) => {
+          const lineCount = code.split(`\n`).length;
+
+          // Shifts each line of the chunk by one, without mapping the banner
+          return {value: {code: `/* banner */\n${code}`, map: JSON.stringify({
+            version: 3,
+            sources: [`chunk.js`],
+            names: [],
+            mappings: `;${Array.from({length: lineCount}, (_, index) => index === 0 ? `AAAA` : `AACA`).join(`;`)}`,
+          })}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const res = await app.bundle(app.locatorFromUrl(ENTRY_URL)!);
+      const map = JSON.parse(res.value!.map);
+
+      expect(map.sources).toContain(`/_dev/file/app/fixtures/chunks/entry.ts`);
+      expect(map.mappings).toMatch(/^;/);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should let the generate hooks emit extra files`, async () => {
+    const app = makeHookedApplication({
+      onGenerateBundle: [{
+        regexp: ``,
+        cb: async ({bundle}: OnGenerateBundleArgs) => ({
+          value: {...bundle, files: [...bundle.files, {filename: `report.json`, mimeType: `application/json`, code: `{"ok":true}`}]},
+          dependencies: [],
+        }),
+      }],
+    });
+
+    try {
+      const server = new Server(app, {pageFolder: null});
+
+      const bundleRes = await server.handle({method: `GET`, url: new URL(`/_dev/bundle/app/fixtures/chunks/entry.ts`, `https://example.org`), body: null});
+      expect(bundleRes.code ?? 200).toEqual(200);
+
+      const fileRes = await server.handle({method: `GET`, url: new URL(`/_dev/chunk/report.json`, `https://example.org`), body: null});
+      expect(fileRes.body!.toString()).toEqual(`{"ok":true}`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should report the errors returned by the generate hooks`, async () => {
+    const app = makeHookedApplication({
+      onGenerateBundle: [{
+        regexp: ``,
+        cb: async () => {
+          throw new Error(`Upload failed`);
+        },
+      }],
+    });
+
+    try {
+      const res = await app.bundle(app.locatorFromUrl(ENTRY_URL)!);
+      expect(res.error?.diagnostics[0].message).toContain(`Upload failed`);
+    } finally {
+      app.dispose();
+    }
+  });
+});
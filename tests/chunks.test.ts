@@ -0,0 +1,134 @@
+import {OnGenerateBundleArgs}                       from '@esfuse/compiler';
+
+import {makeHookedApplication, makeTestApplication} from './helpers';
+
+const ENTRY_PATH = `fixtures/chunks/entry.ts`;
+
+const bundleWithChunks = async (chunks: Record<string, Array<string>>) => {
+  const app = makeTestApplication();
+  try {
+    const res = await app.bundle(app.locatorFromUrl(`/_dev/file/app/${ENTRY_PATH}`)!, {chunks});
+    if (res.error)
+      throw new Error(res.error.diagnostics.map(diagnostic => diagnostic.message).join(`\n`));
+
+    return res.value;
+  } finally {
+    app.dispose();
+  }
+};
+
+describe(`Manual chunks`, () => {
+  it(`should move the modules matching a chunk pattern into their own chunk`, async () => {
+    const bundle = await bundleWithChunks({
+      vendor: [`fixtures/chunks/vendor/**`],
+    });
+
+    expect(bundle.chunks).toEqual([
+      expect.objectContaining({name: `vendor`, filename: expect.stringMatching(/^vendor\.[0-9a-f]+\.js$/)}),
+    ]);
+
+    const [vendor] = bundle.chunks;
+
+    expect(vendor.code).toContain(`/_dev/file/app/fixtures/chunks/vendor/greet.ts`);
+    expect(vendor.code).not.toContain(`/_dev/file/app/fixtures/chunks/local.ts`);
+
+    expect(bundle.code).toContain(`/_dev/file/app/fixtures/chunks/local.ts`);
+    expect(bundle.code).not.toContain(`/_dev/file/app/fixtures/chunks/vendor/greet.ts`);
+  });
+
+  it(`should accept the patterns with an explicit ./ prefix`, async () => {
+    const bundle = await bundleWithChunks({
+      vendor: [`./fixtures/chunks/vendor/*.ts`],
+    });
+
+    expect(bundle.chunks.map(chunk => chunk.name)).toEqual([`vendor`]);
+  });
+
+  it(`should put the modules matching multiple groups in the first group by name`, async () => {
+    const bundle = await bundleWithChunks({
+      zzz: [`fixtures/chunks/**`],
+      aaa: [`fixtures/chunks/vendor/**`],
+    });
+
+    const aaa = bundle.chunks.find(chunk => chunk.name === `aaa`)!;
+    const zzz = bundle.chunks.find(chunk => chunk.name === `zzz`)!;
+
+    expect(aaa.code).toContain(`/_dev/file/app/fixtures/chunks/vendor/greet.ts`);
+    expect(zzz.code).toContain(`/_dev/file/app/fixtures/chunks/local.ts`);
+    expect(zzz.code).not.toContain(`/_dev/file/app/fixtures/chunks/vendor/greet.ts`);
+  });
+
+  it(`should keep the entry point in the entry chunk`, async () => {
+    const bundle = await bundleWithChunks({
+      everything: [`fixtures/chunks/**`],
+    });
+
+    expect(bundle.code).toContain(`/_dev/file/app/${ENTRY_PATH}`);
+    expect(bundle.chunks[0].code).not.toContain(`/_dev/file/app/${ENTRY_PATH}`);
+  });
+
+  it(`should not create chunks that no module matches`, async () => {
+    const bundle = await bundleWithChunks({
+      vendor: [`fixtures/chunks/missing/**`],
+    });
+
+    expect(bundle.chunks).toEqual([]);
+    expect(bundle.code).not.toContain(`registerChunks`);
+  });
+
+  it(`should register the chunk filenames and integrities in the entry`, async () => {
+    const bundle = await bundleWithChunks({
+      vendor: [`fixtures/chunks/vendor/**`],
+    });
+
+    const [vendor] = bundle.chunks;
+
+    expect(bundle.code).toContain(JSON.stringify(vendor.filename));
+    expect(bundle.code).toContain(JSON.stringify(vendor.integrity));
+  });
+
+  it(`should reference the source map of each chunk`, async () => {
+    const bundle = await bundleWithChunks({
+      vendor: [`fixtures/chunks/vendor/**`],
+    });
+
+    const [vendor] = bundle.chunks;
+    expect(vendor.code).toContain(`\n//# sourceMappingURL=${vendor.filename}.map\n`);
+  });
+
+  it(`should evaluate the modules moved into the chunks`, async () => {
+    const app = makeTestApplication();
+    try {
+      app.config.chunks = {
+        vendor: [`fixtures/chunks/vendor/**`],
+      };
+
+      const mod = await app.run(app.locatorFromUrl(`/_dev/file/app/${ENTRY_PATH}`)!) as any;
+      expect(mod.message).toEqual(`Hello world!`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should prevent the generate bundle hooks from changing the named chunks`, async () => {
+    const app = makeHookedApplication({
+      onGenerateBundle: [{
+        regexp: ``,
+        cb: async ({bundle}: OnGenerateBundleArgs) => ({
+          value: {...bundle, chunks: bundle.chunks.map(chunk => ({...chunk, code: `/* banner */\n${chunk.code}`}))},
+          dependencies: [],
+        }),
+      }],
+    });
+
+    try {
+      const res = await app.bundle(app.locatorFromUrl(`/_dev/file/app/${ENTRY_PATH}`)!, {
+        chunks: {vendor: [`fixtures/chunks/vendor/**`]},
+      });
+
+      expect(res.error?.diagnostics[0].message).toContain(`A generate bundle hook changed the named chunks`);
+    } finally {
+      app.dispose();
+    }
+  });
+});
@@ -0,0 +1,40 @@
+import {SwcBuiltins}                  from '@esfuse/compiler';
+
+import {makeTestHandle, transformUrl} from './helpers';
+
+const transformFixture = async (name: string, builtins: Partial<SwcBuiltins>) => {
+  const handle = makeTestHandle();
+
+  try {
+    const {code} = await transformUrl(handle, `/_dev/file/app/fixtures/css-in-js/${name}`, {
+      builtins: {emotion: false, graphqlTag: false, styledComponents: false, ...builtins},
+    });
+
+    return code;
+  } finally {
+    handle.dispose();
+  }
+};
+
+describe(`CSS-in-JS`, () => {
+  it(`should add the display names and component ids to styled components`, async () => {
+    const code = await transformFixture(`styled-components.ts`, {styledComponents: true});
+
+    expect(code).toContain(`withConfig`);
+    expect(code).toMatch(/displayName: "Button"/);
+    expect(code).toMatch(/componentId: "sc-/);
+  });
+
+  it(`should add the labels to the emotion components`, async () => {
+    const code = await transformFixture(`emotion.ts`, {emotion: true});
+
+    expect(code).toMatch(/label: "Button"/);
+    expect(code).toMatch(/target: "e/);
+  });
+
+  it(`should leave the components alone when the transforms are disabled`, async () => {
+    const code = await transformFixture(`styled-components.ts`, {});
+
+    expect(code).not.toContain(`withConfig`);
+  });
+});
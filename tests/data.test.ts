@@ -0,0 +1,30 @@
+import {npath, PortablePath, ppath} from '@yarnpkg/fslib';
+import {createRequire}              from 'module';
+
+import {runFixtureBuild}            from './helpers';
+
+describe(`Data modules`, () => {
+  it(`should expose the data itself to CommonJS consumers`, async () => {
+    await runFixtureBuild(`build-data`, {
+      include: [`*.json`, `*.toml`],
+    }, async distFolder => {
+      const require = createRequire(npath.fromPortablePath(ppath.join(distFolder, `package.json` as PortablePath)));
+
+      expect(require(`./config.js`)).toEqual({
+        name: `config`,
+      });
+
+      const data = require(`./data.js`);
+      expect(data.name).toEqual(`data`);
+
+      expect(Object.getPrototypeOf(data)).toBe(Object.prototype);
+      expect(Object.prototype.hasOwnProperty.call(data, `__proto__`)).toEqual(true);
+    });
+  });
+
+  it(`should report non-finite numbers instead of turning them into null`, async () => {
+    await expect(runFixtureBuild(`build-data-non-finite`, {
+      include: [`*.yml`],
+    }, async () => {})).rejects.toThrow(`Non-finite numbers can't be represented in data modules (at limits.max)`);
+  });
+});
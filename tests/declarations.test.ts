@@ -0,0 +1,41 @@
+import {PortablePath, ppath, xfs}     from '@yarnpkg/fslib';
+
+import {readDistFile, runFixtureBuild} from './helpers';
+
+describe(`Declarations`, () => {
+  it(`should match the declaration extension with the source extension`, async () => {
+    await runFixtureBuild(`build-declarations`, {
+      include: [`*.ts`, `*.mts`],
+      declarations: true,
+    }, async distFolder => {
+      expect(xfs.existsSync(ppath.join(distFolder, `index.d.ts` as PortablePath))).toEqual(true);
+      expect(xfs.existsSync(ppath.join(distFolder, `format.d.mts` as PortablePath))).toEqual(true);
+      expect(xfs.existsSync(ppath.join(distFolder, `format.d.ts` as PortablePath))).toEqual(false);
+    });
+  });
+
+  it(`should emit a declaration file for each variant of a dual build`, async () => {
+    await runFixtureBuild(`build-declarations`, {
+      include: [`*.ts`],
+      declarations: true,
+      dual: true,
+    }, async distFolder => {
+      expect(xfs.existsSync(ppath.join(distFolder, `index.d.mts` as PortablePath))).toEqual(true);
+      expect(xfs.existsSync(ppath.join(distFolder, `index.d.cts` as PortablePath))).toEqual(true);
+    });
+  });
+
+  it(`should keep the local declarations referenced by the exported ones`, async () => {
+    await runFixtureBuild(`build-declarations`, {
+      include: [`*.ts`],
+      declarations: true,
+    }, async distFolder => {
+      const declaration = await readDistFile(distFolder, `index.d.ts`);
+
+      expect(declaration).toMatch(/^interface Options \{/m);
+      expect(declaration).toMatch(/^declare class Greeter \{/m);
+      expect(declaration).toMatch(/^export declare function createGreeter\(options: Options\): Greeter;/m);
+      expect(declaration).toMatch(/^export \{\s*\};/m);
+    });
+  });
+});
@@ -0,0 +1,149 @@
+import {npath, PortablePath, ppath, xfs}                                                from '@yarnpkg/fslib';
+import {createRequire}                                                                 from 'module';
+
+import {listDistFiles, readDistFile, readDistJson, runFixtureBuild, withFixtureProject} from './helpers';
+
+describe(`Emit`, () => {
+  it(`should mirror the source layout in the dist folder`, async () => {
+    await runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+      copy: [`assets/*`],
+    }, async distFolder => {
+      expect(await listDistFiles(distFolder)).toEqual([
+        `assets/logo.txt`,
+        `index.js`,
+        `manifest.json`,
+        `package.json`,
+        `utils/format.js`,
+      ]);
+
+      const index = await readDistFile(distFolder, `index.js`);
+      expect(index).toMatch(/["']\.\/utils\/format\.js["']/);
+    });
+  });
+
+  it(`should list each emitted file in the manifest`, async () => {
+    await runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+      copy: [`assets/*`],
+    }, async distFolder => {
+      const integrity = expect.stringMatching(/^sha384-/);
+
+      expect(await readDistJson(distFolder, `manifest.json`)).toEqual({
+        [`/_dev/file/app/sources/assets/logo.txt`]: [
+          {file: `./assets/logo.txt`, integrity},
+        ],
+        [`/_dev/file/app/sources/index.ts`]: [
+          {file: `./index.js`, integrity},
+        ],
+        [`/_dev/file/app/sources/utils/format.ts`]: [
+          {file: `./utils/format.js`, integrity},
+        ],
+      });
+    });
+  });
+
+  it(`should rewrite the imports to the emitted files`, async () => {
+    await runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+    }, async distFolder => {
+      const require = createRequire(npath.fromPortablePath(ppath.join(distFolder, `package.json` as PortablePath)));
+      expect(require(`./index.js`).greeting).toEqual(`Hello world`);
+    });
+  });
+
+  it(`should name the emitted files by content hash`, async () => {
+    await runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+      hashFilenames: true,
+    }, async distFolder => {
+      const files = await listDistFiles(distFolder);
+
+      const indexFile = files.find(file => file.startsWith(`index.`) && file.endsWith(`.js`))!;
+      const formatFile = files.find(file => file.startsWith(`utils/format.`))!;
+
+      expect(indexFile).toMatch(/^index\.[0-9a-f]+\.js$/);
+      expect(formatFile).toMatch(/^utils\/format\.[0-9a-f]+\.js$/);
+
+      const index = await readDistFile(distFolder, indexFile);
+      expect(index).toContain(JSON.stringify(`./${formatFile}`));
+
+      expect(await readDistJson(distFolder, `manifest.json`)).toMatchObject({
+        [`/_dev/file/app/sources/index.ts`]: [{file: `./${indexFile}`}],
+        [`/_dev/file/app/sources/utils/format.ts`]: [{file: `./${formatFile}`}],
+      });
+
+      const require = createRequire(npath.fromPortablePath(ppath.join(distFolder, `package.json` as PortablePath)));
+      expect(require(`./${indexFile}`).greeting).toEqual(`Hello world`);
+    });
+  });
+
+  it(`should list the format of each variant in the manifest`, async () => {
+    await runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+      dual: true,
+    }, async distFolder => {
+      expect(await listDistFiles(distFolder)).toEqual([
+        `index.cjs`,
+        `index.mjs`,
+        `manifest.json`,
+        `package.json`,
+        `utils/format.cjs`,
+        `utils/format.mjs`,
+      ]);
+
+      const integrity = expect.stringMatching(/^sha384-/);
+
+      expect(await readDistJson(distFolder, `manifest.json`)).toEqual({
+        [`/_dev/file/app/sources/index.ts`]: [
+          {file: `./index.mjs`, integrity, format: `esm`},
+          {file: `./index.cjs`, integrity, format: `cjs`},
+        ],
+        [`/_dev/file/app/sources/utils/format.ts`]: [
+          {file: `./utils/format.mjs`, integrity, format: `esm`},
+          {file: `./utils/format.cjs`, integrity, format: `cjs`},
+        ],
+      });
+    });
+  });
+
+  it(`should report the builds cancelled before emitting`, async () => {
+    const controller = new AbortController();
+    controller.abort();
+
+    await expect(runFixtureBuild(`build-layout`, {
+      include: [`*.ts`],
+    }, async () => {}, {
+      signal: controller.signal,
+    })).rejects.toThrow(`The build was cancelled`);
+  });
+
+  it(`should remove the streamed files when the build fails`, async () => {
+    await withFixtureProject(`build-partial`, {
+      include: [`*.ts`],
+    }, async (app, distFolder) => {
+      const streamed: Array<string> = [];
+
+      await expect(app.build(`lib`, {
+        onModule: module => {
+          if (module.value)
+            streamed.push(module.locator.url);
+        },
+      })).rejects.toThrow(`Build failed`);
+
+      // The module callbacks and the build result go through different
+      // queues; we let the pending callbacks run before checking them
+      await new Promise(resolve => setImmediate(resolve));
+
+      // The valid module doesn't have any import, so it's written as soon
+      // as it's compiled, regardless of the other modules
+      expect(streamed).toEqual([`/_dev/file/app/sources/valid.ts`]);
+
+      const files = xfs.existsSync(distFolder)
+        ? await listDistFiles(distFolder)
+        : [];
+
+      expect(files).toEqual([]);
+    });
+  });
+});
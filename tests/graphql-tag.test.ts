@@ -0,0 +1,69 @@
+import {makeTestHandle, transformUrl} from './helpers';
+
+const transformFixture = async (name: string) => {
+  const handle = makeTestHandle();
+
+  try {
+    const {code} = await transformUrl(handle, `/_dev/file/app/fixtures/graphql-tag/${name}`, {
+      builtins: {emotion: false, graphqlTag: true, styledComponents: false},
+    });
+
+    return code;
+  } finally {
+    handle.dispose();
+  }
+};
+
+const getDocument = (code: string) => {
+  const match = code.match(/JSON\.parse\((['"].*['"])\)/s);
+  if (!match)
+    throw new Error(`Expected the tag to be precompiled`);
+
+  return JSON.parse(new Function(`return ${match[1]}`)());
+};
+
+describe(`GraphQL tags`, () => {
+  it(`should convert the variable definitions`, async () => {
+    const document = getDocument(await transformFixture(`query.ts`));
+    const [id, limit] = document.definitions[0].variableDefinitions;
+
+    expect(id.variable).toEqual({kind: `Variable`, name: {kind: `Name`, value: `id`}});
+    expect(id.defaultValue).toEqual({kind: `IntValue`, value: `1`});
+    expect(limit.type).toEqual({kind: `NonNullType`, type: {kind: `NamedType`, name: {kind: `Name`, value: `Int`}}});
+    expect(limit.defaultValue).toBeUndefined();
+  });
+
+  it(`should serialize the numbers`, async () => {
+    const document = getDocument(await transformFixture(`query.ts`));
+    const [, , seed, ratio] = document.definitions[0].selectionSet.selections[0].arguments;
+
+    expect(seed.value).toEqual({kind: `IntValue`, value: `1234567890`});
+    expect(ratio.value).toEqual({kind: `FloatValue`, value: `1500`});
+  });
+
+  it(`should dedent block strings`, async () => {
+    const document = getDocument(await transformFixture(`query.ts`));
+    const text = document.definitions[0].selectionSet.selections[0].arguments[4];
+
+    expect(text.value).toEqual({kind: `StringValue`, value: `hello\n  world`, block: false});
+  });
+
+  it(`should attach the source location to the document`, async () => {
+    const document = getDocument(await transformFixture(`query.ts`));
+
+    expect(document.loc.start).toEqual(0);
+    expect(document.loc.end).toEqual(document.loc.source.body.length);
+    expect(document.loc.source.body).toContain(`fragment Result on SearchResult`);
+  });
+
+  it(`should leave schema definitions to graphql-tag`, async () => {
+    const code = await transformFixture(`schema.ts`);
+
+    expect(code).not.toContain(`JSON.parse`);
+    expect(code).toContain(`type Query`);
+  });
+
+  it(`should report the syntax errors`, async () => {
+    await expect(transformFixture(`invalid.ts`)).rejects.toThrow(`Syntax Error`);
+  });
+});
@@ -1,12 +1,14 @@
-import {npath, PortablePath, ppath, xfs} from '@yarnpkg/fslib';
-import {execFile}                        from 'child_process';
-import {createRequire}                   from 'module';
-import path                              from 'path';
-import {promisify}                       from 'util';
-import vm                                from 'vm';
-
-import {Project}                         from 'esfuse/sources/Project';
-import {Request, Server}                 from 'esfuse/sources/Server';
+import {ModuleFormat, OnTransformSwcOpts, ProjectDefinition, ProjectHandle} from '@esfuse/compiler';
+import {npath, PortablePath, ppath, xfs}                                      from '@yarnpkg/fslib';
+import {execFile}                                                             from 'child_process';
+import {createRequire}                                                        from 'module';
+import path                                                                   from 'path';
+import {promisify}                                                            from 'util';
+import vm                                                                     from 'vm';
+
+import {BuildConfig}                                                          from 'esfuse/sources/Config';
+import {Project}                                                              from 'esfuse/sources/Project';
+import {Request, Server}                                                      from 'esfuse/sources/Server';
 
 declare module 'vm' {
   class SourceTextModule {
@@ -21,6 +23,66 @@ export function makeTestApplication() {
   return new Project(path.dirname(__dirname));
 }
 
+// Regular application, with extra hooks registered on top of its own
+export function makeHookedApplication(definition: Partial<ProjectDefinition> | ((app: Project) => Partial<ProjectDefinition>)) {
+  const app = makeTestApplication();
+
+  app.handle.reconfigure({
+    ...app.getProjectDefinition(),
+    ...typeof definition === `function` ? definition(app) : definition,
+  });
+
+  return app;
+}
+
+// Bare definition, for the tests that need to control the hooks themselves
+export function getTestDefinition(definition: Partial<ProjectDefinition> = {}): ProjectDefinition {
+  return {
+    root: path.dirname(__dirname),
+    namespaces: {},
+    onResolve: [],
+    onFetch: [],
+    onTransform: [],
+    onRenderChunk: [],
+    onGenerateBundle: [],
+    transforms: {},
+    ...definition,
+  };
+}
+
+export function makeTestHandle(definition: Partial<ProjectDefinition> = {}) {
+  return ProjectHandle.create(getTestDefinition(definition));
+}
+
+// Raw result, for the tests that check the errors themselves
+export async function getTransformResult(handle: ProjectHandle, url: string, swc: Partial<OnTransformSwcOpts> = {}) {
+  return await handle.transform({
+    locator: handle.getLocatorFromUrl(url)!,
+    opts: {
+      staticResolutions: {},
+      userData: {},
+      swc: {
+        emitDeclarations: false,
+        moduleFormat: ModuleFormat.Esm,
+        promisifyBody: false,
+        plugins: [],
+        builtins: {emotion: false, graphqlTag: false, styledComponents: false},
+        useEsfuseRuntime: false,
+        ...swc,
+      },
+    },
+  });
+}
+
+export async function transformUrl(handle: ProjectHandle, url: string, swc: Partial<OnTransformSwcOpts> = {}) {
+  const res = await getTransformResult(handle, url, swc);
+
+  if (res.error)
+    throw new Error(res.error.diagnostics.map(diagnostic => diagnostic.message).join(`\n`));
+
+  return res.value!;
+}
+
 export async function makeAppRunner(app: Project) {
   const server = new Server(app, {
     pageFolder: null,
@@ -92,6 +154,62 @@ export async function installProjectLayout(layout: Record<string, string>, fn: (
   });
 }
 
+// Project whose "lib" build compiles the given fixture into its dist folder
+export async function withFixtureProject(fixture: string, buildConfig: BuildConfig, fn: (app: Project, distFolder: PortablePath) => Promise<void>) {
+  const fixtureFolder = ppath.join(npath.toPortablePath(path.dirname(__dirname)), `fixtures` as PortablePath, fixture as PortablePath);
+
+  await installProjectLayout({}, async tmpDir => {
+    await xfs.copyPromise(ppath.join(tmpDir, `sources` as PortablePath), fixtureFolder);
+
+    const app = new Project(npath.fromPortablePath(tmpDir));
+    try {
+      app.config.patterns = {
+        sourceFolder: `sources`,
+        distFolder: `dist`,
+      };
+
+      app.config.builds = {
+        lib: buildConfig,
+      };
+
+      await fn(app, ppath.join(tmpDir, `dist` as PortablePath));
+    } finally {
+      app.dispose();
+    }
+  });
+}
+
+export async function runFixtureBuild(fixture: string, buildConfig: BuildConfig, fn: (distFolder: PortablePath) => Promise<void>, buildOpts?: Parameters<Project[`build`]>[1]) {
+  await withFixtureProject(fixture, buildConfig, async (app, distFolder) => {
+    await app.build(`lib`, buildOpts);
+    await fn(distFolder);
+  });
+}
+
+export async function listDistFiles(folder: PortablePath, prefix = ``): Promise<Array<string>> {
+  const files: Array<string> = [];
+
+  for (const entry of await xfs.readdirPromise(folder, {withFileTypes: true})) {
+    const relativePath = `${prefix}${entry.name}`;
+
+    if (entry.isDirectory()) {
+      files.push(...await listDistFiles(ppath.join(folder, entry.name), `${relativePath}/`));
+    } else {
+      files.push(relativePath);
+    }
+  }
+
+  return files.sort();
+}
+
+export async function readDistFile(distFolder: PortablePath, fileName: string) {
+  return await xfs.readFilePromise(ppath.join(distFolder, fileName as PortablePath), `utf8`);
+}
+
+export async function readDistJson(distFolder: PortablePath, fileName: string) {
+  return await xfs.readJsonPromise(ppath.join(distFolder, fileName as PortablePath));
+}
+
 const execFileP = promisify(execFile);
 
 export async function execvp(file: string, args: Array<string>, {cwd}: {cwd: PortablePath}) {
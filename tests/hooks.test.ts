@@ -0,0 +1,290 @@
+import {OnFetchArgs, OnResolveArgs, ProjectDefinition, ProjectHandle, ResolutionKind, ResolveResult} from '@esfuse/compiler';
+
+import {Project}                                                                                     from 'esfuse/sources/Project';
+
+import {getTestDefinition, getTransformResult, makeHookedApplication, makeTestHandle, transformUrl}  from './helpers';
+
+const makeHookedHandle = (onFetch: ProjectDefinition[`onFetch`], {hookTimeout}: {hookTimeout?: number} = {}) => {
+  return makeTestHandle({onFetch, hookTimeout});
+};
+
+const sleep = (ms: number) => new Promise(resolve => setTimeout(resolve, ms));
+
+const virtualFetch = (flavor: string) => async (args: OnFetchArgs) => ({
+  value: {
+    locator: args.locator,
+    mimeType: `text/javascript`,
+    source: `export const flavor = ${JSON.stringify(flavor)};\n`,
+  },
+  dependencies: [],
+});
+
+const transformCode = async (handle: ProjectHandle, url: string) => {
+  return (await transformUrl(handle, url)).code;
+};
+
+describe(`Hook params`, () => {
+  it(`should only run the hooks whose params are present`, async () => {
+    const handle = makeHookedHandle([{
+      regexp: ``,
+      params: [{name: `virtual`}],
+      cb: virtualFetch(`virtual`),
+    }]);
+
+    try {
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts?virtual`)).resolves.toContain(`"virtual"`);
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts`)).resolves.not.toContain(`"virtual"`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should only run the hooks whose params have the expected value`, async () => {
+    const handle = makeHookedHandle([{
+      regexp: ``,
+      params: [{name: `flavor`, value: `a`}],
+      cb: virtualFetch(`a`),
+    }, {
+      regexp: ``,
+      params: [{name: `flavor`, value: `b`}],
+      cb: virtualFetch(`b`),
+    }]);
+
+    try {
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts?flavor=a`)).resolves.toContain(`"a"`);
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts?flavor=b`)).resolves.toContain(`"b"`);
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts?flavor=c`)).resolves.not.toContain(`export const flavor`);
+    } finally {
+      handle.dispose();
+    }
+  });
+});
+
+describe(`Hook errors`, () => {
+  it(`should point the errors thrown by the fetch hooks to the fetched module`, async () => {
+    const handle = makeHookedHandle([{
+      regexp: ``,
+      cb: async () => {
+        throw new Error(`Fetch failed`);
+      },
+    }]);
+
+    try {
+      const url = `/_dev/file/app/fixtures/log.ts`;
+      const res = await getTransformResult(handle, url);
+
+      expect(res.error?.diagnostics).toEqual([{
+        message: expect.stringContaining(`Fetch failed`),
+        highlights: [expect.objectContaining({source: url, subject: expect.stringMatching(/^onFetch\[0\]/)})],
+      }]);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should point the errors thrown by the resolve hooks to the issuer`, async () => {
+    const handle = makeTestHandle({
+      onResolve: [{
+        regexp: ``,
+        cb: async () => {
+          throw new Error(`Resolve failed`);
+        },
+      }],
+    });
+
+    try {
+      const issuer = handle.getLocatorFromUrl(`/_dev/file/app/fixtures/log.ts`)!;
+      const res = await handle.resolve({kind: ResolutionKind.ImportDeclaration, request: `./other`, issuer, opts: {forceParams: [], userData: {}}});
+
+      expect(res.error?.diagnostics).toEqual([{
+        message: expect.stringContaining(`Resolve failed`),
+        highlights: [expect.objectContaining({source: issuer.url, subject: expect.stringMatching(/^onResolve\[0\]/)})],
+      }]);
+    } finally {
+      handle.dispose();
+    }
+  });
+});
+
+describe(`Hook timings`, () => {
+  it(`should fail the hooks that exceed the default timeout`, async () => {
+    const handle = makeHookedHandle([{
+      regexp: ``,
+      cb: async (args: OnFetchArgs) => {
+        await sleep(500);
+        return virtualFetch(`slow`)(args);
+      },
+    }], {hookTimeout: 50});
+
+    try {
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts`)).rejects.toThrow(`timed out after 50ms`);
+      expect(handle.getHookStats()).toEqual([expect.objectContaining({calls: 1, timeouts: 1})]);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should exclude the time spent in the following hooks from the stats`, async () => {
+    const handle: ProjectHandle = makeHookedHandle([{
+      regexp: ``,
+      cb: async (args: OnFetchArgs, nextId: number) => {
+        return await handle.fetchNext(nextId, args);
+      },
+    }, {
+      regexp: ``,
+      cb: async (args: OnFetchArgs) => {
+        await sleep(200);
+        return virtualFetch(`slow`)(args);
+      },
+    }]);
+
+    try {
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts`)).resolves.toContain(`"slow"`);
+
+      const [slowest, wrapper] = handle.getHookStats();
+      expect(slowest.name).toMatch(/^onFetch\[1\]/);
+      expect(slowest.totalMs).toBeGreaterThanOrEqual(150);
+      expect(wrapper.name).toMatch(/^onFetch\[0\]/);
+      expect(wrapper.totalMs).toBeLessThan(100);
+    } finally {
+      handle.dispose();
+    }
+  });
+});
+
+describe(`Reconfiguration`, () => {
+  it(`should apply the new hooks to the following operations`, async () => {
+    const handle = makeHookedHandle([{regexp: ``, cb: virtualFetch(`before`)}]);
+
+    try {
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts`)).resolves.toContain(`"before"`);
+
+      handle.reconfigure(getTestDefinition({onFetch: [{regexp: ``, cb: virtualFetch(`after`)}]}));
+
+      await expect(transformCode(handle, `/_dev/file/app/fixtures/log.ts`)).resolves.toContain(`"after"`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should keep the hook stats across reconfigurations`, async () => {
+    const handle = makeHookedHandle([{regexp: `log`, cb: virtualFetch(`before`)}]);
+
+    try {
+      await transformCode(handle, `/_dev/file/app/fixtures/log.ts`);
+
+      handle.reconfigure(getTestDefinition({onFetch: [{regexp: `log`, cb: virtualFetch(`after`)}]}));
+
+      await transformCode(handle, `/_dev/file/app/fixtures/log.ts`);
+
+      expect(handle.getHookStats()).toEqual([
+        expect.objectContaining({name: `onFetch[0] (log)`, calls: 2}),
+      ]);
+    } finally {
+      handle.dispose();
+    }
+  });
+});
+
+describe(`Hook chaining`, () => {
+  const resolveFromLog = async (app: Project, request: string) => {
+    return await app.handle.resolve({
+      kind: ResolutionKind.ImportDeclaration,
+      request,
+      issuer: app.locatorFromUrl(`/_dev/file/app/fixtures/log.ts`)!,
+      opts: {forceParams: [], userData: {}},
+    });
+  };
+
+  it(`should let the resolve hooks wrap the following ones`, async () => {
+    const calls: Array<string> = [];
+
+    const app = makeHookedApplication(app => ({
+      onResolve: [{
+        regexp: ``,
+        cb: app.resolveHook(async (args, next) => {
+          calls.push(`outer`);
+
+          const res = await next(args);
+          if (!res.value)
+            return res;
+
+          return {...res, value: {...res.value, locator: app.locatorFromUrl(`${res.value.locator.url}?tagged`)!}};
+        }),
+      }, {
+        regexp: ``,
+        cb: app.resolveHook(async (args: OnResolveArgs, next) => {
+          calls.push(`inner`);
+
+          return args.request === `virtual-log`
+            ? await next({...args, request: `./log`})
+            : undefined;
+        }),
+      }],
+    }));
+
+    try {
+      const res = await resolveFromLog(app, `virtual-log`);
+
+      expect(calls).toEqual([`outer`, `inner`]);
+      expect(res.value?.locator.url).toEqual(`/_dev/file/app/fixtures/log.ts?tagged`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should expire the continuations of the hooks that threw`, async () => {
+    let savedNext: ((args: OnResolveArgs) => Promise<unknown>) | undefined;
+
+    const app = makeHookedApplication(app => ({
+      onResolve: [{
+        regexp: ``,
+        cb: app.resolveHook(async (args, next) => {
+          savedNext = next;
+          throw new Error(`Resolution failed`);
+        }),
+      }],
+    }));
+
+    try {
+      const res = await resolveFromLog(app, `./log`);
+      expect(res.error?.diagnostics[0].message).toContain(`Resolution failed`);
+
+      await expect(savedNext!({kind: ResolutionKind.ImportDeclaration, request: `./log`, opts: {forceParams: [], userData: {}}})).resolves.toMatchObject({
+        error: {diagnostics: [expect.objectContaining({message: `The resolve continuation has expired`})]},
+      });
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should expire the continuations of the hooks that timed out`, async () => {
+    let lateRes: Promise<ResolveResult> | undefined;
+
+    const app = makeHookedApplication(app => ({
+      onResolve: [{
+        regexp: ``,
+        timeout: 50,
+        cb: app.resolveHook(async (args, next) => {
+          await sleep(200);
+
+          lateRes = next(args);
+          return await lateRes;
+        }),
+      }],
+    }));
+
+    try {
+      const res = await resolveFromLog(app, `./log`);
+      expect(res.error?.diagnostics[0].message).toContain(`timed out after 50ms`);
+
+      await sleep(300);
+
+      await expect(lateRes).resolves.toMatchObject({
+        error: {diagnostics: [expect.objectContaining({message: `The resolve continuation has expired`})]},
+      });
+    } finally {
+      app.dispose();
+    }
+  });
+});
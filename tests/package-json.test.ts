@@ -0,0 +1,64 @@
+import {readDistJson, runFixtureBuild} from './helpers';
+
+describe(`Package.json rewriting`, () => {
+  it(`should remap the main field without adding an exports field`, async () => {
+    await runFixtureBuild(`build-package-json-main`, {
+      include: [`*.ts`],
+    }, async distFolder => {
+      const pkg = await readDistJson(distFolder, `package.json`);
+
+      expect(pkg).toEqual({
+        name: `build-package-json-main`,
+        main: `./index.js`,
+      });
+    });
+  });
+
+  it(`should remap each export condition to the matching output`, async () => {
+    await runFixtureBuild(`build-package-json-conditions`, {
+      include: [`*.ts`],
+      declarations: true,
+    }, async distFolder => {
+      const pkg = await readDistJson(distFolder, `package.json`);
+
+      expect(pkg).toEqual({
+        name: `build-package-json-conditions`,
+        main: `./index.js`,
+        exports: {
+          [`.`]: {
+            types: `./index.d.ts`,
+            import: `./index.js`,
+            require: `./index.js`,
+          },
+          [`./package.json`]: `./package.json`,
+        },
+      });
+    });
+  });
+
+  it(`should split exports between the variants of a dual build`, async () => {
+    await runFixtureBuild(`build-package-json-exports`, {
+      include: [`*.ts`],
+      dual: true,
+    }, async distFolder => {
+      const pkg = await readDistJson(distFolder, `package.json`);
+
+      expect(pkg).toEqual({
+        name: `build-package-json-exports`,
+        main: `./index.cjs`,
+        exports: {
+          [`.`]: {
+            import: `./index.mjs`,
+            require: `./index.cjs`,
+          },
+          [`./features/*`]: {
+            import: `./features/*.mjs`,
+            require: `./features/*.cjs`,
+          },
+          [`./features/internal/*`]: null,
+          [`./package.json`]: `./package.json`,
+        },
+      });
+    });
+  });
+});
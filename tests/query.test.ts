@@ -0,0 +1,91 @@
+import {OnFetchArgs}                                                     from '@esfuse/compiler';
+
+import {makeAppRunner, makeTestApplication, makeTestHandle, transformUrl} from './helpers';
+
+describe(`Query parameters`, () => {
+  it(`should import the raw text of a file through ?raw`, async () => {
+    const app = makeTestApplication();
+    try {
+      const {query} = await makeAppRunner(app);
+
+      const {text} = await query.get(`/_dev/bundle/app/fixtures/import-raw.ts`);
+      expect(text).toEqual(`Hello world\n`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should import a file as a data url through ?inline`, async () => {
+    const app = makeTestApplication();
+    try {
+      const {query} = await makeAppRunner(app);
+
+      const {dataUrl} = await query.get(`/_dev/bundle/app/fixtures/import-raw.ts`);
+      expect(dataUrl).toEqual(`data:text/plain;base64,${Buffer.from(`Hello world\n`).toString(`base64`)}`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should import the url of a file through ?transform=url`, async () => {
+    const app = makeTestApplication();
+    try {
+      const {query} = await makeAppRunner(app);
+
+      const {url} = await query.get(`/_dev/bundle/app/fixtures/import-url.ts`);
+      expect(url).toEqual(`/_dev/file/app/fixtures/query-assets/hello.txt`);
+    } finally {
+      app.dispose();
+    }
+  });
+
+  it(`should only select the transforms opted into bare flags through them`, async () => {
+    const handle = makeTestHandle();
+    try {
+      const {code} = await transformUrl(handle, `/_dev/file/app/fixtures/log.ts?url`);
+      expect(code).toContain(`console.log`);
+
+      const {code: transformed} = await transformUrl(handle, `/_dev/file/app/fixtures/log.ts?transform=url`);
+      expect(transformed).toContain(`"/_dev/file/app/fixtures/log.ts"`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should accept padded base64 from the fetch hooks when inlining binary files`, async () => {
+    const handle = makeTestHandle({
+      onFetch: [{
+        regexp: `/pixel\\.png`,
+        cb: async (args: OnFetchArgs) => ({
+          value: {locator: args.locator, mimeType: `image/png`, source: Buffer.from(`hi`).toString(`base64`)},
+          dependencies: [],
+        }),
+      }],
+    });
+
+    try {
+      const {code} = await transformUrl(handle, `/_dev/file/app/fixtures/query-assets/pixel.png?inline`);
+      expect(code).toContain(`"data:image/png;base64,aGk="`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should report the binary sources that aren't valid base64`, async () => {
+    const handle = makeTestHandle({
+      onFetch: [{
+        regexp: `/pixel\\.png`,
+        cb: async (args: OnFetchArgs) => ({
+          value: {locator: args.locator, mimeType: `image/png`, source: `not base64!`},
+          dependencies: [],
+        }),
+      }],
+    });
+
+    try {
+      await expect(transformUrl(handle, `/_dev/file/app/fixtures/query-assets/pixel.png?inline`)).rejects.toThrow();
+    } finally {
+      handle.dispose();
+    }
+  });
+});
@@ -0,0 +1,92 @@
+import {ModuleFormat, ProjectHandle, SwcPlugin} from '@esfuse/compiler';
+
+import {makeTestHandle, transformUrl}           from './helpers';
+
+const ENTRY_URL = `/_dev/file/app/fixtures/swc-plugins/entry.ts`;
+
+async function transformWithPlugins(handle: ProjectHandle, plugins: Array<SwcPlugin>) {
+  return await handle.transform({
+    locator: handle.getLocatorFromUrl(ENTRY_URL)!,
+    opts: {
+      staticResolutions: {},
+      userData: {},
+      swc: {
+        emitDeclarations: false,
+        moduleFormat: ModuleFormat.Esm,
+        promisifyBody: false,
+        plugins,
+        builtins: {emotion: false, graphqlTag: false, styledComponents: false},
+        useEsfuseRuntime: false,
+      },
+    },
+  });
+}
+
+describe(`SWC plugins`, () => {
+  it(`should report the plugins that can't be found as compilation errors`, async () => {
+    const handle = makeTestHandle();
+    try {
+      const res = await transformWithPlugins(handle, [{
+        path: `./fixtures/swc-plugins/missing.wasm`,
+        options: {},
+        include: [],
+      }]);
+
+      expect(res.value).toBeUndefined();
+      expect(res.error).toEqual({
+        diagnostics: [expect.objectContaining({
+          message: expect.stringContaining(`missing.wasm`),
+        })],
+      });
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should report the plugins that aren't valid Wasm modules as compilation errors`, async () => {
+    const handle = makeTestHandle();
+    try {
+      const res = await transformWithPlugins(handle, [{
+        path: `./fixtures/swc-plugins/invalid.wasm`,
+        options: {},
+        include: [],
+      }]);
+
+      expect(res.value).toBeUndefined();
+      expect(res.error?.diagnostics).toHaveLength(1);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should skip the plugins whose globs don't match the file`, async () => {
+    const handle = makeTestHandle();
+    try {
+      const res = await transformWithPlugins(handle, [{
+        path: `./fixtures/swc-plugins/invalid.wasm`,
+        options: {},
+        include: [`./sources/**`],
+      }]);
+
+      expect(res.error).toBeUndefined();
+      expect(res.value?.code).toContain(`42`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should apply the plugins whose globs match the file`, async () => {
+    const handle = makeTestHandle();
+    try {
+      await expect(transformUrl(handle, ENTRY_URL, {
+        plugins: [{
+          path: `./fixtures/swc-plugins/invalid.wasm`,
+          options: {},
+          include: [`./fixtures/swc-plugins/*.ts`],
+        }],
+      })).rejects.toThrow();
+    } finally {
+      handle.dispose();
+    }
+  });
+});
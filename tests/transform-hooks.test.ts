@@ -0,0 +1,171 @@
+import {OnTransformHookArgs, ResolutionKind, TransformStage} from '@esfuse/compiler';
+
+import {makeTestHandle, transformUrl}                       from './helpers';
+
+const LOG_URL = `/_dev/file/app/fixtures/log.ts`;
+
+describe(`Transform hooks`, () => {
+  it(`should run the hooks before and after the built-in transforms`, async () => {
+    const calls: Array<[TransformStage, string]> = [];
+
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          calls.push([stage, data.code]);
+          return undefined;
+        },
+      }],
+    });
+
+    try {
+      await transformUrl(handle, LOG_URL);
+
+      expect(calls).toEqual([
+        [TransformStage.Before, expect.stringContaining(`console.log(\`foo\`)`)],
+        [TransformStage.After, expect.stringContaining(`console.log`)],
+      ]);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should compile the code returned by the before hooks`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.Before)
+            return undefined;
+
+          return {value: {...data, code: `const value: number = 42;\nexport {value};\n`}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const {code} = await transformUrl(handle, LOG_URL);
+
+      expect(code).toContain(`42`);
+      expect(code).not.toContain(`: number`);
+      expect(code).not.toContain(`console.log`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should chain the hooks, each one receiving the output of the previous one`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.After)
+            return undefined;
+
+          return {value: {...data, code: `${data.code}/* first */\n`}, dependencies: []};
+        },
+      }, {
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.After)
+            return undefined;
+
+          return {value: {...data, code: `${data.code}/* second */\n`}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const {code} = await transformUrl(handle, LOG_URL);
+      expect(code).toMatch(/\/\* first \*\/\n\/\* second \*\/\n$/);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should keep the imports declared by the before hooks`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.Before)
+            return undefined;
+
+          return {value: {...data, imports: [...data.imports, {kind: ResolutionKind.ImportDeclaration, specifier: `./runtime-only`, span: {start: {row: 0, col: 0}, end: {row: 0, col: 0}}, optional: true}]}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const {imports} = await transformUrl(handle, LOG_URL);
+      expect(imports.map(entry => entry.specifier)).toContain(`./runtime-only`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should compose the source map returned by the before hooks`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.Before)
+            return undefined;
+
+          // The hook prepends a line, which its map doesn't trace back to
+          // anything in the original file
+          return {value: {...data, code: `// prelude\n${data.code}`, map: JSON.stringify({
+            version: 3,
+            sources: [LOG_URL],
+            names: [],
+            mappings: `;AAAA`,
+          })}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      const {map} = await transformUrl(handle, LOG_URL);
+      expect(JSON.parse(map!).sources).toContain(LOG_URL);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should report the invalid source maps returned by the hooks`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async ({stage, data}: OnTransformHookArgs) => {
+          if (stage !== TransformStage.Before)
+            return undefined;
+
+          return {value: {...data, map: `not a source map`}, dependencies: []};
+        },
+      }],
+    });
+
+    try {
+      await expect(transformUrl(handle, LOG_URL)).rejects.toThrow(`source map`);
+    } finally {
+      handle.dispose();
+    }
+  });
+
+  it(`should report the errors thrown by the hooks`, async () => {
+    const handle = makeTestHandle({
+      onTransform: [{
+        regexp: ``,
+        cb: async () => {
+          throw new Error(`Instrumentation failed`);
+        },
+      }],
+    });
+
+    try {
+      await expect(transformUrl(handle, LOG_URL)).rejects.toThrow(`Instrumentation failed`);
+    } finally {
+      handle.dispose();
+    }
+  });
+});